@@ -94,6 +94,70 @@ where
         true
     }
 
+    // same as `hit`, but with the reciprocal of the ray direction already computed so it can be
+    // reused for many boxes (e.g. bvh traversal)
+    pub fn hit_inverse(
+        &self,
+        origin: &Vector<T, N>,
+        inv_direction: &Vector<T, N>,
+        mut t_min: T,
+        mut t_max: T,
+    ) -> bool
+    where
+        T: Float,
+    {
+        for (ax, int) in self.intervals.iter().enumerate() {
+            let t0 = (int.min - origin[ax]) * inv_direction[ax];
+            let t1 = (int.max - origin[ax]) * inv_direction[ax];
+
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+
+            if t_min >= t_max {
+                return false;
+            }
+        }
+        true
+    }
+
+    // make sure no side of the box is thinner than `delta` (e.g. for planar objects)
+    pub fn pad_to_minimums(mut self, delta: T) -> Self
+    where
+        T: Float,
+    {
+        for int in self.intervals.iter_mut() {
+            if int.size() < delta {
+                int.expand(delta / T::from(2.0).unwrap());
+            }
+        }
+        self
+    }
+
+    pub fn centroid(&self) -> Vector<T, N>
+    where
+        T: Float,
+    {
+        let two = T::from(2.0).unwrap();
+        Vector::new(array::from_fn(|i| {
+            (self.intervals[i].min + self.intervals[i].max) / two
+        }))
+    }
+
+    // sum of the area of the faces, only meaningful for N = 3
+    pub fn surface_area(&self) -> T
+    where
+        T: Float,
+    {
+        let sizes: [T; N] = array::from_fn(|i| self.intervals[i].size().max(T::zero()));
+        let mut area = T::zero();
+        for i in 0..N {
+            for j in (i + 1)..N {
+                area = area + sizes[i] * sizes[j];
+            }
+        }
+        area + area
+    }
+
     pub fn longest_axis(&self) -> usize {
         let mut longest = 0;
         let mut max_length = T::zero();
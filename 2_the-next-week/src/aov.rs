@@ -0,0 +1,235 @@
+use std::array;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::color::Color;
+use crate::material::Material;
+use crate::output;
+use crate::ray_tracer::{Dimension, Image};
+use crate::util;
+use crate::vec::Vector;
+
+type Vec3 = Vector<f64, 3>;
+
+// auxiliary per-pixel buffers (arbitrary output variables) rendered next to the beauty image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    // base color of the first hit, the clamped background for the missed rays
+    Albedo,
+    // world space shading normal of the first hit
+    Normal,
+    // distance from the camera along the view direction
+    Depth,
+    // world space position of the first hit
+    Position,
+    // id of the material of the first hit, 0 for the background
+    Id,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 5] = [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Position, Aov::Id];
+
+    // comma separated list of aov names, or "all"
+    pub fn parse_list(s: &str) -> Result<Vec<Aov>, String> {
+        match s.trim().to_lowercase().as_str() {
+            "all" => Ok(Aov::ALL.to_vec()),
+            "" | "none" => Ok(Vec::new()),
+            list => list.split(',').map(|name| name.trim().parse()).collect(),
+        }
+    }
+
+    // the aov is written next to the output, e.g. "image.png" -> "image.albedo.png"
+    pub fn path(&self, output: &Path) -> PathBuf {
+        output::sibling_path(output, &self.to_string())
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "albedo" => Ok(Aov::Albedo),
+            "normal" => Ok(Aov::Normal),
+            "depth" => Ok(Aov::Depth),
+            "position" => Ok(Aov::Position),
+            "id" => Ok(Aov::Id),
+            _ => Err(format!("Unknown aov '{}'", s)),
+        }
+    }
+}
+
+impl Display for Aov {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Aov::Albedo => write!(f, "albedo"),
+            Aov::Normal => write!(f, "normal"),
+            Aov::Depth => write!(f, "depth"),
+            Aov::Position => write!(f, "position"),
+            Aov::Id => write!(f, "id"),
+        }
+    }
+}
+
+// first-hit values of a pixel, averaged over its samples (the id is the one of the first sample)
+#[derive(Clone, Debug, PartialEq)]
+pub struct AovPixel {
+    pub albedo: Color,
+    pub normal: Vec3,
+    pub position: Vec3,
+    // infinite if none of the samples hit anything
+    pub depth: f64,
+    pub id: usize,
+}
+
+impl Default for AovPixel {
+    fn default() -> Self {
+        Self {
+            albedo: Color::new_one(0.0),
+            normal: Vec3::default(),
+            position: Vec3::default(),
+            depth: f64::INFINITY,
+            id: 0,
+        }
+    }
+}
+
+// key of the material while rendering, the keys are replaced by small ids in `AovBuffers::new`
+pub fn material_key(material: Option<&dyn Material>) -> usize {
+    match material {
+        Some(material) => material as *const dyn Material as *const () as usize,
+        None => usize::MAX,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AovBuffers {
+    pub pixels: Vec<AovPixel>,
+    pub dimension: Dimension,
+}
+
+impl AovBuffers {
+    // renumbers the material keys to ids in the order they first appear (row by row), so the ids
+    // don't depend on where the materials are in memory. Objects sharing a material share an id.
+    pub fn new(mut pixels: Vec<AovPixel>, dimension: Dimension) -> Self {
+        let mut ids = HashMap::from([(0, 0)]);
+        for pixel in pixels.iter_mut() {
+            let next_id = ids.len();
+            pixel.id = *ids.entry(pixel.id).or_insert(next_id);
+        }
+
+        Self { pixels, dimension }
+    }
+
+    // Builds an image of one aov. With `display` the values are remapped to [0, 1] to be viewed in
+    // an ldr format, otherwise the raw values are kept (the depth of the background becomes 0).
+    pub fn image(&self, aov: Aov, display: bool) -> Image {
+        let pixels = match (aov, display) {
+            (Aov::Albedo, _) => self.pixels.iter().map(|p| p.albedo.clone()).collect(),
+            (Aov::Normal, false) => self.map(|p| Color::from(p.normal)),
+            (Aov::Normal, true) => self.map(|p| Color::from(p.normal * 0.5 + 0.5)),
+            (Aov::Depth, false) => self.map(|p| match p.depth.is_finite() {
+                true => Color::new_one(p.depth),
+                false => Color::new_one(0.0),
+            }),
+            (Aov::Depth, true) => {
+                // near is black, far and the background are white
+                let max = self.hits().map(|p| p.depth).fold(0.0, f64::max);
+                self.map(|p| match p.depth.is_finite() && max > 0.0 {
+                    true => Color::new_one(p.depth / max),
+                    false => Color::new_one(1.0),
+                })
+            }
+            (Aov::Position, false) => self.map(|p| Color::from(p.position)),
+            (Aov::Position, true) => {
+                // normalized to the bounds of the visible points
+                let (min, max) = self.hits().fold(
+                    (
+                        Vec3::new_one(f64::INFINITY),
+                        Vec3::new_one(f64::NEG_INFINITY),
+                    ),
+                    |(min, max), p| {
+                        (
+                            Vec3::new(array::from_fn(|i| min[i].min(p.position[i]))),
+                            Vec3::new(array::from_fn(|i| max[i].max(p.position[i]))),
+                        )
+                    },
+                );
+                let extent = (max - min).transform(|v| v.max(1e-9));
+                self.map(|p| match p.depth.is_finite() {
+                    true => Color::from((p.position - min) / extent),
+                    false => Color::new_one(0.0),
+                })
+            }
+            (Aov::Id, false) => self.map(|p| Color::new_one(p.id as f64)),
+            (Aov::Id, true) => self.map(|p| id_color(p.id)),
+        };
+
+        Image {
+            pixels,
+            dimension: self.dimension.clone(),
+            aovs: None,
+            sample_counts: None,
+        }
+    }
+
+    fn map(&self, f: impl Fn(&AovPixel) -> Color) -> Vec<Color> {
+        self.pixels.iter().map(f).collect()
+    }
+
+    fn hits(&self) -> impl Iterator<Item = &AovPixel> {
+        self.pixels.iter().filter(|p| p.depth.is_finite())
+    }
+}
+
+// distinct looking color for an id, black for the background
+fn id_color(id: usize) -> Color {
+    if id == 0 {
+        return Color::new_one(0.0);
+    }
+    let hash = util::derive_seed(0, &[id as u64]);
+    let channel = |shift: u32| 0.2 + 0.8 * ((hash >> shift) & 0xff) as f64 / 255.0;
+    Color::new([channel(0), channel(8), channel(16)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Aov::parse_list("all"), Ok(Aov::ALL.to_vec()));
+        assert_eq!(Aov::parse_list("depth, id"), Ok(vec![Aov::Depth, Aov::Id]));
+        assert!(Aov::parse_list("albedo,colour").is_err());
+        for aov in Aov::ALL {
+            assert_eq!(aov.to_string().parse(), Ok(aov));
+        }
+
+        assert_eq!(
+            Aov::Normal.path(Path::new("out/image.png")),
+            Path::new("out/image.normal.png")
+        );
+        assert_eq!(Aov::Id.path(Path::new("image")), Path::new("image.id"));
+    }
+
+    #[test]
+    fn test_ids() {
+        let pixel = |id| AovPixel {
+            id,
+            ..Default::default()
+        };
+        let dimension = Dimension {
+            width: 5,
+            height: 1,
+        };
+        let buffers = AovBuffers::new(
+            vec![pixel(0xf00), pixel(0), pixel(0xba0), pixel(0xf00), pixel(7)],
+            dimension,
+        );
+
+        let ids = buffers.pixels.iter().map(|p| p.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 0, 2, 1, 3]);
+    }
+}
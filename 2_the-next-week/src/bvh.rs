@@ -1,3 +1,6 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
 use crate::aabb::AABB;
 use crate::hittable::{HitResult, Hittable};
 use crate::interval::Interval;
@@ -7,106 +10,535 @@ use crate::ray::Ray;
 type AABB3 = AABB<f64, 3>;
 type Ray3 = Ray<f64, 3>;
 
-pub enum BvhNodeElement {
-    Leaf(Box<dyn Hittable>),
-    Node(Box<BvhNode>),
+// relative cost of visiting a node compared to intersecting an object
+const TRAVERSAL_COST: f64 = 0.125;
+const SAH_BIN_COUNT: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitMethod {
+    // split at the median of the longest axis
+    Median,
+    // binned surface area heuristic
+    Sah,
 }
 
-// Bounding Volume Hierarchy (basically a binary tree)
-// TODO: A node can only exist if it has at least one child. One of the node can be not an Option.
-pub struct BvhNode {
-    left: Option<BvhNodeElement>,
-    right: Option<BvhNodeElement>,
+impl FromStr for SplitMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "median" => Ok(SplitMethod::Median),
+            "sah" => Ok(SplitMethod::Sah),
+            _ => Err(format!("Unknown split method '{}'", s)),
+        }
+    }
+}
+
+impl Display for SplitMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitMethod::Median => write!(f, "median"),
+            SplitMethod::Sah => write!(f, "sah"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BvhParams {
+    pub split_method: SplitMethod,
+    pub max_leaf_size: usize,
+}
+
+// the median split with one object per leaf, the way the tree was always built
+impl Default for BvhParams {
+    fn default() -> Self {
+        Self {
+            split_method: SplitMethod::Median,
+            max_leaf_size: 1,
+        }
+    }
+}
+
+// tree used while building, flattened into `BvhNode` once done
+enum BuildNode {
+    Leaf {
+        objects: Vec<Box<dyn Hittable>>,
+        bbox: AABB3,
+    },
+    Interior {
+        left: Box<BuildNode>,
+        right: Box<BuildNode>,
+        axis: usize,
+        bbox: AABB3,
+    },
+}
+
+impl BuildNode {
+    fn bbox(&self) -> &AABB3 {
+        match self {
+            BuildNode::Leaf { bbox, .. } => bbox,
+            BuildNode::Interior { bbox, .. } => bbox,
+        }
+    }
+}
+
+enum LinearContent {
+    // objects are `objects[first..first + count]`
+    Leaf { first: u32, count: u32 },
+    // the first child is stored right after the node, `axis` is the axis the node was split on
+    Interior { second: u32, axis: u8 },
+}
+
+// a node of the flattened tree, small enough to fit in a cache line
+struct LinearNode {
     bbox: AABB3,
+    content: LinearContent,
+}
+
+// Bounding Volume Hierarchy (basically a binary tree), stored as a contiguous array of nodes in
+// depth-first order
+pub struct BvhNode {
+    nodes: Vec<LinearNode>,
+    objects: Vec<Box<dyn Hittable>>,
+    depth: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct BvhStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub max_leaf_size: usize,
 }
 
+// traversal stack size that covers any reasonably balanced tree without allocating
+const STACK_SIZE: usize = 64;
+
 impl BvhNode {
     pub fn new(objects: Vec<Box<dyn Hittable>>) -> BvhNode {
-        Self::split(objects)
+        Self::with_params(objects, &BvhParams::default())
+    }
+
+    pub fn with_params(objects: Vec<Box<dyn Hittable>>, params: &BvhParams) -> BvhNode {
+        let max_leaf_size = params.max_leaf_size.max(1);
+        let root = match params.split_method {
+            SplitMethod::Median => Self::split_median(objects, max_leaf_size),
+            SplitMethod::Sah => Self::split_sah(objects, max_leaf_size),
+        };
+
+        let mut bvh = BvhNode {
+            nodes: Vec::new(),
+            objects: Vec::new(),
+            depth: 0,
+        };
+        bvh.flatten(root, 1);
+        bvh
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let leaf_sizes = self.nodes.iter().filter_map(|n| match n.content {
+            LinearContent::Leaf { count, .. } => Some(count as usize),
+            LinearContent::Interior { .. } => None,
+        });
+        BvhStats {
+            node_count: self.nodes.len(),
+            leaf_count: leaf_sizes.clone().count(),
+            max_depth: self.depth,
+            max_leaf_size: leaf_sizes.max().unwrap_or(0),
+        }
+    }
+
+    // appends the subtree in depth-first order, returns the index of its root
+    fn flatten(&mut self, node: BuildNode, depth: usize) -> usize {
+        let index = self.nodes.len();
+        self.depth = self.depth.max(depth);
+
+        match node {
+            BuildNode::Leaf { objects, bbox } => {
+                self.nodes.push(LinearNode {
+                    bbox,
+                    content: LinearContent::Leaf {
+                        first: self.objects.len() as u32,
+                        count: objects.len() as u32,
+                    },
+                });
+                self.objects.extend(objects);
+            }
+            BuildNode::Interior {
+                left,
+                right,
+                axis,
+                bbox,
+            } => {
+                // the second child index is only known after the first subtree is flattened
+                self.nodes.push(LinearNode {
+                    bbox,
+                    content: LinearContent::Interior { second: 0, axis: 0 },
+                });
+                self.flatten(*left, depth + 1);
+                let second = self.flatten(*right, depth + 1);
+                self.nodes[index].content = LinearContent::Interior {
+                    second: second as u32,
+                    axis: axis as u8,
+                };
+            }
+        }
+
+        index
     }
 
-    // TODO: Find a more concrete way to subdivide the objects (read some papers or articles)
-    fn split(mut objects: Vec<Box<dyn Hittable>>) -> BvhNode {
+    fn bounding_box_of(objects: &[Box<dyn Hittable>]) -> AABB3 {
         let mut bbox = AABB3::empty();
         objects.iter().for_each(|o| {
-            bbox.combine(&o.bounding_box());
+            bbox.combine(o.bounding_box());
         });
+        bbox
+    }
 
-        if objects.len() == 1 {
-            return Self {
-                left: Some(BvhNodeElement::Leaf(objects.pop().unwrap())),
-                right: None,
-                bbox,
-            };
+    fn leaf(objects: Vec<Box<dyn Hittable>>, bbox: AABB3) -> BuildNode {
+        BuildNode::Leaf { objects, bbox }
+    }
+
+    fn interior(left: BuildNode, right: BuildNode, axis: usize) -> BuildNode {
+        let bbox = left.bbox().clone().combine_new(right.bbox());
+        BuildNode::Interior {
+            left: Box::new(left),
+            right: Box::new(right),
+            axis,
+            bbox,
         }
-        if objects.len() == 2 {
-            return Self {
-                left: Some(BvhNodeElement::Leaf(objects.pop().unwrap())),
-                right: Some(BvhNodeElement::Leaf(objects.pop().unwrap())),
-                bbox,
-            };
+    }
+
+    fn split_median(mut objects: Vec<Box<dyn Hittable>>, max_leaf_size: usize) -> BuildNode {
+        let bbox = Self::bounding_box_of(&objects);
+
+        if objects.len() <= max_leaf_size {
+            return Self::leaf(objects, bbox);
         }
 
         let axis = bbox.longest_axis();
-        objects.sort_by(move |a: &Box<dyn Hittable>, b: &Box<dyn Hittable>| {
+        objects.sort_by(move |a, b| {
             let a_interval = a.bounding_box().axis_interval(axis);
             let b_interval = b.bounding_box().axis_interval(axis);
             a_interval.min.partial_cmp(&b_interval.min).unwrap()
         });
 
         let mid = objects.len() / 2;
-        let left = Self::split(objects.drain(..mid).collect::<Vec<_>>());
-        let right = Self::split(objects);
+        let left = Self::split_median(objects.drain(..mid).collect::<Vec<_>>(), max_leaf_size);
+        let right = Self::split_median(objects, max_leaf_size);
 
-        Self {
-            left: Some(BvhNodeElement::Node(Box::new(left))),
-            right: Some(BvhNodeElement::Node(Box::new(right))),
-            bbox,
-        }
+        Self::interior(left, right, axis)
     }
-}
 
-impl Hittable for BvhNode {
-    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult> {
-        if !self.bbox.hit(ray.clone(), t_range.clone()) {
-            return None;
+    // Objects are put into bins by their centroid along each axis, then the split between bins
+    // with the lowest SAH cost is chosen. See: "On fast Construction of SAH-based Bounding Volume
+    // Hierarchies" (Wald, 2007).
+    fn split_sah(objects: Vec<Box<dyn Hittable>>, max_leaf_size: usize) -> BuildNode {
+        let bbox = Self::bounding_box_of(&objects);
+
+        if objects.len() <= 1 {
+            return Self::leaf(objects, bbox);
         }
 
-        let left_hit = self.left.as_ref().and_then(|n| match n {
-            BvhNodeElement::Leaf(h) => h.hit(ray.clone(), t_range.clone()),
-            BvhNodeElement::Node(n) => n.hit(ray.clone(), t_range.clone()),
+        let centroids = objects
+            .iter()
+            .map(|o| o.bounding_box().centroid())
+            .collect::<Vec<_>>();
+        let mut centroid_bounds = AABB3::empty();
+        centroids.iter().for_each(|c| {
+            centroid_bounds.combine(&AABB3::from_points(*c, *c));
         });
 
-        let t_max = left_hit
-            .as_ref()
-            .map(|r| r.record.t_value)
-            .unwrap_or(t_range.max);
-        let new_t_range = Interval::new(t_range.min, t_max);
+        let bin_of = |axis: usize, centroid: f64| {
+            let interval = centroid_bounds.axis_interval(axis);
+            let relative = (centroid - interval.min) / interval.size();
+            ((relative * SAH_BIN_COUNT as f64) as usize).min(SAH_BIN_COUNT - 1)
+        };
 
-        let right_hit = self.right.as_ref().and_then(|n| match n {
-            BvhNodeElement::Leaf(h) => h.hit(ray.clone(), new_t_range.clone()),
-            BvhNodeElement::Node(n) => n.hit(ray.clone(), new_t_range.clone()),
-        });
+        // (cost, axis, number of bins on the left side)
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            if centroid_bounds.axis_interval(axis).size() <= 0.0 {
+                continue;
+            }
+
+            let mut bins: [(usize, AABB3); SAH_BIN_COUNT] =
+                std::array::from_fn(|_| (0, AABB3::empty()));
+            for (object, centroid) in objects.iter().zip(centroids.iter()) {
+                let bin = &mut bins[bin_of(axis, centroid[axis])];
+                bin.0 += 1;
+                bin.1.combine(object.bounding_box());
+            }
+
+            // sweep from the right to get the right side cost of every split
+            let mut right_area = [0.0; SAH_BIN_COUNT];
+            let mut right_count = [0usize; SAH_BIN_COUNT];
+            let mut accumulated = (0, AABB3::empty());
+            for i in (1..SAH_BIN_COUNT).rev() {
+                accumulated.0 += bins[i].0;
+                accumulated.1.combine(&bins[i].1);
+                right_count[i] = accumulated.0;
+                right_area[i] = accumulated.1.surface_area();
+            }
 
-        match (left_hit, right_hit) {
-            (None, None) => None,
-            (None, Some(r)) => Some(r),
-            (Some(l), None) => Some(l),
-            (Some(l), Some(r)) => {
-                if l.record.t_value < r.record.t_value {
-                    Some(l)
-                } else {
-                    Some(r)
+            let mut accumulated = (0, AABB3::empty());
+            for split in 1..SAH_BIN_COUNT {
+                accumulated.0 += bins[split - 1].0;
+                accumulated.1.combine(&bins[split - 1].1);
+                if accumulated.0 == 0 || right_count[split] == 0 {
+                    continue;
                 }
+
+                let cost = accumulated.1.surface_area() * accumulated.0 as f64
+                    + right_area[split] * right_count[split] as f64;
+                if best.is_none_or(|b| cost < b.0) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        let leaf_cost = objects.len() as f64;
+        let (cost, axis, split) = match best {
+            Some((cost, axis, split)) => {
+                let cost = TRAVERSAL_COST + cost / bbox.surface_area();
+                (cost, axis, split)
             }
+            // all centroids are at the same point, bins can't separate them
+            None if objects.len() <= max_leaf_size => return Self::leaf(objects, bbox),
+            None => return Self::split_median(objects, max_leaf_size),
+        };
+
+        if objects.len() <= max_leaf_size && leaf_cost <= cost {
+            return Self::leaf(objects, bbox);
+        }
+
+        let (left, right): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .zip(centroids)
+            .partition(|(_, c)| bin_of(axis, c[axis]) < split);
+
+        let left = left.into_iter().map(|(o, _)| o).collect::<Vec<_>>();
+        let right = right.into_iter().map(|(o, _)| o).collect::<Vec<_>>();
+
+        Self::interior(
+            Self::split_sah(left, max_leaf_size),
+            Self::split_sah(right, max_leaf_size),
+            axis,
+        )
+    }
+}
+
+impl BvhNode {
+    // `COUNT` is only set for `hit_counted`, so the normal traversal doesn't count anything
+    fn traverse<'a, const COUNT: bool>(
+        &'a self,
+        ray: &Ray3,
+        t_range: &Interval,
+        stack: &mut [u32],
+        visits: &mut usize,
+    ) -> Option<HitResult<'a>> {
+        let inv_direction = ray.direction.transform(|v| 1.0 / v);
+        let mut current_hit = None;
+        let mut t_closest = t_range.max;
+
+        let mut stack_len = 0;
+        let mut index = 0;
+        loop {
+            if COUNT {
+                *visits += 1;
+            }
+            let node = &self.nodes[index];
+
+            if node
+                .bbox
+                .hit_inverse(&ray.origin, &inv_direction, t_range.min, t_closest)
+            {
+                match node.content {
+                    LinearContent::Leaf { first, count } => {
+                        let first = first as usize;
+                        for object in &self.objects[first..first + count as usize] {
+                            let t_range = Interval::new(t_range.min, t_closest);
+                            let hit = match COUNT {
+                                true => object.hit_counted(ray.clone(), t_range, visits),
+                                false => object.hit(ray.clone(), t_range),
+                            };
+                            if let Some(hit) = hit {
+                                t_closest = hit.record.t_value;
+                                current_hit = Some(hit);
+                            }
+                        }
+                    }
+                    LinearContent::Interior { second, axis } => {
+                        // visit the child closer to the ray origin first, so the farther one can
+                        // be culled by the closest hit
+                        let (near, far) = match ray.direction[axis as usize] < 0.0 {
+                            true => (second as usize, index + 1),
+                            false => (index + 1, second as usize),
+                        };
+                        stack[stack_len] = far as u32;
+                        stack_len += 1;
+                        index = near;
+                        continue;
+                    }
+                }
+            }
+
+            match stack_len {
+                0 => break,
+                _ => {
+                    stack_len -= 1;
+                    index = stack[stack_len] as usize;
+                }
+            }
+        }
+
+        current_hit
+    }
+
+    fn traverse_with_stack<const COUNT: bool>(
+        &self,
+        ray: &Ray3,
+        t_range: &Interval,
+        visits: &mut usize,
+    ) -> Option<HitResult<'_>> {
+        // the stack never holds more nodes than the depth of the tree
+        match self.depth <= STACK_SIZE {
+            true => self.traverse::<COUNT>(ray, t_range, &mut [0; STACK_SIZE], visits),
+            false => self.traverse::<COUNT>(ray, t_range, &mut vec![0; self.depth], visits),
         }
     }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        self.traverse_with_stack::<false>(&ray, &t_range, &mut 0)
+    }
+
+    fn hit_counted(
+        &self,
+        ray: Ray3,
+        t_range: Interval,
+        visits: &mut usize,
+    ) -> Option<HitResult<'_>> {
+        self.traverse_with_stack::<true>(&ray, &t_range, visits)
+    }
 
     fn bounding_box(&self) -> &AABB3 {
-        &self.bbox
+        &self.nodes[0].bbox
     }
 
-    fn get_material<'a>(&'a self) -> Option<&'a dyn Material> {
+    fn get_material(&self) -> Option<&dyn Material> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HittableList, Sphere};
+    use crate::util;
+    use crate::vec::Vector;
+
+    fn spheres() -> Vec<Box<dyn Hittable>> {
+        (0..200)
+            .map(|i| {
+                let x = (i % 20) as f64 * 3.0;
+                let z = (i / 20) as f64 * 3.0 + (i % 3) as f64 * 0.5;
+                Box::new(Sphere::new(Vector::new([x, 0.0, z]), 1.0, None)) as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_methods() {
+        for split_method in [SplitMethod::Median, SplitMethod::Sah] {
+            for max_leaf_size in [1, 2, 4, 8] {
+                let params = BvhParams {
+                    split_method,
+                    max_leaf_size,
+                };
+                let bvh = BvhNode::with_params(spheres(), &params);
+                assert!(bvh.stats().max_leaf_size <= max_leaf_size);
+
+                // every sphere is hit from above, at its top
+                for i in 0..200 {
+                    let x = (i % 20) as f64 * 3.0;
+                    let z = (i / 20) as f64 * 3.0 + (i % 3) as f64 * 0.5;
+                    let ray = Ray {
+                        origin: Vector::new([x, 10.0, z]),
+                        direction: Vector::new([0.0, -1.0, 0.0]),
+                        time: 0.0,
+                    };
+                    let hit = bvh.hit(ray, Interval::new(0.001, f64::INFINITY)).unwrap();
+                    assert!((hit.record.point - Vector::new([x, 1.0, z])).near_zero());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_list() {
+        util::seed_rng(11);
+        let bvh = BvhNode::new(spheres());
+        let mut list = HittableList::new();
+        spheres().into_iter().for_each(|s| list.add(s));
+
+        // rays in every octant, so both child orders are used
+        for i in 0..500 {
+            let direction = Vector::new([
+                util::get_random(-1.0, 1.0),
+                util::get_random(-1.0, 1.0),
+                util::get_random(-1.0, 1.0),
+            ]);
+            let ray = Ray {
+                origin: Vector::new([30.0, (i % 5) as f64 * 0.2, 15.0]),
+                direction,
+                time: 0.0,
+            };
+            let t_range = Interval::new(0.001, f64::INFINITY);
+            let expected = list
+                .hit(ray.clone(), t_range.clone())
+                .map(|h| h.record.t_value);
+            let actual = bvh.hit(ray, t_range).map(|h| h.record.t_value);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let bvh = BvhNode::new(Vec::new());
+        let ray = Ray {
+            origin: Vector::new([0.0, 0.0, 0.0]),
+            direction: Vector::new([1.0, 1.0, 1.0]),
+            time: 0.0,
+        };
+
+        assert!(bvh.hit(ray, Interval::new(0.001, f64::INFINITY)).is_none());
+        assert_eq!(bvh.stats().leaf_count, 1);
+    }
+
+    #[test]
+    fn test_visit_count() {
+        let bvh = BvhNode::with_params(spheres(), &BvhParams::default());
+        let ray = Ray {
+            origin: Vector::new([0.0, 10.0, 0.0]),
+            direction: Vector::new([0.0, -1.0, 0.0]),
+            time: 0.0,
+        };
+
+        let mut visits = 0;
+        let hit = bvh.hit_counted(
+            ray.clone(),
+            Interval::new(0.001, f64::INFINITY),
+            &mut visits,
+        );
+        assert!(visits > 0 && visits < bvh.stats().node_count);
+
+        // same hit as the uncounted traversal
+        let expected = bvh.hit(ray, Interval::new(0.001, f64::INFINITY));
+        assert_eq!(
+            hit.map(|h| h.record.t_value),
+            expected.map(|h| h.record.t_value)
+        );
+    }
+}
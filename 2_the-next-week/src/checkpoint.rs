@@ -0,0 +1,142 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::output;
+use crate::ray_tracer::{Accumulator, TracerParams};
+use crate::scenes::SceneOptions;
+
+// Checkpoint of a progressive render, so a render that got killed can continue where it stopped.
+//
+// The random numbers of a sample only depend on the seed, the pixel and the sample index, so the
+// sample counts in the accumulator and the seed in the description are the whole state of the
+// random number generator.
+//
+// Layout: the magic line, the description of the render (`key = value` lines), an empty line and
+// then the accumulator (see `Accumulator::write`).
+
+const MAGIC: &str = "ray tracing checkpoint v1";
+
+// Everything that changes the samples of a pixel. The sampling rate is not part of it, so a
+// finished render can be resumed with more samples.
+pub fn describe(params: &TracerParams, scene_name: &str, options: &SceneOptions) -> String {
+    [
+        ("scene", scene_name.to_string()),
+        ("scene_seed", options.seed.to_string()),
+        ("height", params.height.to_string()),
+        ("aspect_ratio", params.aspect_ratio.to_string()),
+        ("max_depth", params.max_depth.to_string()),
+        ("vfov", params.vfov.to_string()),
+        ("defocus_angle", params.defocus_angle.to_string()),
+        ("focus_distance", params.focus_distance.to_string()),
+        ("look_from", params.look_from.to_string()),
+        ("look_at", params.look_at.to_string()),
+        ("seed", params.seed.to_string()),
+        ("adaptive", format!("{:?}", params.adaptive)),
+        ("lights", params.lights.len().to_string()),
+    ]
+    .iter()
+    .map(|(key, value)| format!("{} = {}\n", key, value))
+    .collect()
+}
+
+// written to a temporary file first, so the previous checkpoint survives if this one is cut short
+pub fn save(path: &Path, description: &str, accumulator: &Accumulator) -> io::Result<()> {
+    let partial_path = output::sibling_path(path, "partial");
+    {
+        let mut file = BufWriter::new(File::create(&partial_path)?);
+        write!(file, "{}\n{}\n", MAGIC, description)?;
+        accumulator.write(&mut file)?;
+        file.flush()?;
+    }
+    fs::rename(&partial_path, path)
+}
+
+// fails if the checkpoint was made with a different description (see `describe`)
+pub fn load(path: &Path, description: &str) -> Result<Accumulator, String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+
+    let mut read_line = || -> Result<String, String> {
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        Ok(line)
+    };
+
+    if read_line()?.trim_end() != MAGIC {
+        return Err("Not a checkpoint file".to_string());
+    }
+
+    let mut saved = String::new();
+    loop {
+        match read_line()?.as_str() {
+            "" => return Err("Truncated checkpoint".to_string()),
+            "\n" => break,
+            line => saved.push_str(line),
+        }
+    }
+
+    if saved != description {
+        let mismatches = saved
+            .lines()
+            .zip(description.lines())
+            .filter(|(saved, current)| saved != current)
+            .map(|(saved, current)| format!("\n  checkpoint: {}\n  current:    {}", saved, current))
+            .collect::<String>();
+        return Err(format!(
+            "Checkpoint doesn't match the current parameters:{}",
+            mismatches
+        ));
+    }
+
+    Accumulator::read(&mut reader).map_err(|e| format!("Failed to read checkpoint: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracer::{ProgressiveParams, RayTracer};
+    use crate::scenes::SCENES;
+    use std::time::Duration;
+
+    #[test]
+    fn test_resume() {
+        let options = SceneOptions::default();
+        let world = SCENES["random-spheres-bouncing"](&options).world;
+        let params = |seed| TracerParams {
+            height: 12,
+            sampling_rate: 6,
+            seed,
+            ..Default::default()
+        };
+        let ray_tracer = RayTracer::new(params(0));
+        let progressive = ProgressiveParams {
+            samples_per_pass: 2,
+            snapshot_interval: Duration::ZERO,
+            time_limit: None,
+        };
+        let description = describe(&params(0), "random-spheres-bouncing", &options);
+        // unique, so concurrent test runs don't share the checkpoint
+        let path = std::env::temp_dir().join(format!(
+            "ray-tracer-test-resume-{}.ckpt",
+            std::process::id()
+        ));
+
+        // checkpoint after the first pass, then continue from there
+        let expected = ray_tracer.render_progressive(&world, &progressive, None, |accumulator| {
+            if accumulator.min_sample_count() == Some(2) {
+                save(&path, &description, accumulator).unwrap();
+            }
+        });
+        let resumed = load(&path, &description).unwrap();
+        assert_eq!(resumed.min_sample_count(), Some(2));
+        let image = ray_tracer.render_progressive(&world, &progressive, Some(resumed), |_| {});
+        assert_eq!(image.pixels, expected.pixels);
+        assert_eq!(image.sample_counts, expected.sample_counts);
+
+        let other = describe(&params(1), "random-spheres-bouncing", &options);
+        let error = load(&path, &other).err().unwrap();
+        assert!(error.contains("seed = 0") && error.contains("seed = 1"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
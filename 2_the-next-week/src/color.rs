@@ -106,6 +106,15 @@ impl<T: VecElement> Color<T> {
     {
         self.transform(|x| util::linear_to_gamma(x.into()).into())
     }
+
+    // relative luminance of a linear color (Rec. 709 primaries)
+    pub fn luminance(&self) -> f64
+    where
+        T: Into<f64>,
+    {
+        let [r, g, b] = self.0.data.map(|x| x.into());
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,228 @@
+use crate::aov::AovBuffers;
+use crate::color::Color;
+use crate::ray_tracer::{Dimension, Image};
+
+// 1D B3 spline, the 5x5 kernel is its outer product
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// added to the albedo before dividing by it, so black surfaces don't blow up
+const ALBEDO_EPSILON: f64 = 1e-3;
+
+#[derive(Clone, Debug)]
+pub struct DenoiseParams {
+    // the filter covers 4 * 2^iterations pixels
+    pub iterations: u32,
+    // how much the (demodulated) colors may differ, halved after every iteration
+    pub sigma_color: f64,
+    pub sigma_normal: f64,
+    // relative to the depth of the center pixel
+    pub sigma_depth: f64,
+    pub sigma_albedo: f64,
+}
+
+impl Default for DenoiseParams {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 4.0,
+            sigma_normal: 0.3,
+            sigma_depth: 0.05,
+            sigma_albedo: 0.2,
+        }
+    }
+}
+
+// Edge-aware à-trous wavelet filter guided by the first-hit buffers, see: "Edge-Avoiding À-Trous
+// Wavelet Transform for fast Global Illumination Filtering" (Dammertz et al., 2010).
+//
+// The color is divided by the albedo first so the textures are not blurred, only the lighting is
+// filtered. Pixels are only mixed with pixels that have a similar color, normal, depth and albedo.
+pub fn denoise(image: &Image, guides: &AovBuffers, params: &DenoiseParams) -> Image {
+    let Dimension { width, height } = image.dimension;
+    assert_eq!(image.pixels.len(), guides.pixels.len());
+
+    let albedo = guides
+        .pixels
+        .iter()
+        .map(|p| p.albedo.clone() + ALBEDO_EPSILON)
+        .collect::<Vec<_>>();
+    let mut current = image
+        .pixels
+        .iter()
+        .zip(&albedo)
+        .map(|(color, albedo)| color.clone() / albedo.clone())
+        .collect::<Vec<_>>();
+
+    for iteration in 0..params.iterations {
+        let step = 1i64 << iteration;
+        let sigma_color = params.sigma_color / (1u64 << iteration) as f64;
+        let mut filtered = Vec::with_capacity(current.len());
+        // colors are compared after x / (1 + x) so a few very bright samples don't stop the filter
+        let compressed = current
+            .iter()
+            .map(|c| c.transform(|v| v / (1.0 + v)))
+            .collect::<Vec<_>>();
+
+        for row in 0..height as i64 {
+            for col in 0..width as i64 {
+                let index = (row * width as i64 + col) as usize;
+                let center = &guides.pixels[index];
+
+                let mut sum = Color::new_one(0.0);
+                let mut weight_sum = 0.0;
+                for (dy, ky) in KERNEL.iter().enumerate() {
+                    for (dx, kx) in KERNEL.iter().enumerate() {
+                        let y = row + (dy as i64 - 2) * step;
+                        let x = col + (dx as i64 - 2) * step;
+                        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                            continue;
+                        }
+
+                        let other_index = (y * width as i64 + x) as usize;
+                        let other = &guides.pixels[other_index];
+
+                        let depth_weight = match (center.depth.is_finite(), other.depth.is_finite())
+                        {
+                            (true, true) => {
+                                let difference = (center.depth - other.depth).abs()
+                                    / (params.sigma_depth * center.depth.max(1e-6));
+                                (-difference * difference).exp()
+                            }
+                            // both are background
+                            (false, false) => 1.0,
+                            _ => 0.0,
+                        };
+                        if depth_weight <= 0.0 {
+                            continue;
+                        }
+
+                        let color_weight = gaussian(
+                            distance_squared(&compressed[index], &compressed[other_index]),
+                            sigma_color,
+                        );
+                        let normal_weight = gaussian(
+                            (center.normal - other.normal).length_squared(),
+                            params.sigma_normal,
+                        );
+                        let albedo_weight = gaussian(
+                            distance_squared(&albedo[index], &albedo[other_index]),
+                            params.sigma_albedo,
+                        );
+
+                        let weight =
+                            kx * ky * depth_weight * color_weight * normal_weight * albedo_weight;
+                        sum = sum + current[other_index].clone() * weight;
+                        weight_sum += weight;
+                    }
+                }
+
+                // the center pixel always has a weight, unless something is NaN
+                filtered.push(match weight_sum > 0.0 {
+                    true => sum / weight_sum,
+                    false => current[index].clone(),
+                });
+            }
+        }
+
+        current = filtered;
+    }
+
+    Image {
+        pixels: current
+            .into_iter()
+            .zip(albedo)
+            .map(|(irradiance, albedo)| irradiance * albedo)
+            .collect(),
+        dimension: image.dimension.clone(),
+        aovs: image.aovs.clone(),
+        sample_counts: image.sample_counts.clone(),
+    }
+}
+
+fn gaussian(distance_squared: f64, sigma: f64) -> f64 {
+    (-distance_squared / (sigma * sigma)).exp()
+}
+
+fn distance_squared(a: &Color, b: &Color) -> f64 {
+    let difference = a.clone() - b.clone();
+    difference.r().powi(2) + difference.g().powi(2) + difference.b().powi(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aov::AovPixel;
+    use crate::util;
+    use crate::vec::Vector;
+
+    // left half faces +z and is white, right half faces +x and is red, every pixel is noisy
+    fn noisy_image(width: u32, height: u32) -> (Image, AovBuffers) {
+        util::seed_rng(3);
+        let dimension = Dimension { width, height };
+        let is_left = |i: usize| (i as u32 % width) < width / 2;
+
+        let guides = (0..width * height)
+            .map(|i| AovPixel {
+                albedo: match is_left(i as usize) {
+                    true => Color::new_one(1.0),
+                    false => Color::new([1.0, 0.0, 0.0]),
+                },
+                normal: match is_left(i as usize) {
+                    true => Vector::new([0.0, 0.0, 1.0]),
+                    false => Vector::new([1.0, 0.0, 0.0]),
+                },
+                depth: 5.0,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let pixels = guides
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let lighting = if is_left(i) { 0.8 } else { 0.2 };
+                p.albedo.clone() * (lighting * util::get_random(0.5, 1.5))
+            })
+            .collect();
+
+        let image = Image {
+            pixels,
+            dimension: dimension.clone(),
+            aovs: None,
+            sample_counts: None,
+        };
+        (image, AovBuffers::new(guides, dimension))
+    }
+
+    fn mean_error(pixels: &[Color], expected: f64) -> f64 {
+        pixels.iter().map(|c| (c.g() - expected).abs()).sum::<f64>() / pixels.len() as f64
+    }
+
+    #[test]
+    fn test_denoise() {
+        let (width, height) = (32, 16);
+        let (image, guides) = noisy_image(width, height);
+        let denoised = denoise(&image, &guides, &DenoiseParams::default());
+
+        let left = |pixels: &[Color]| {
+            pixels
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| (*i as u32 % width) < width / 2)
+                .map(|(_, c)| c.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // less noise on the left half
+        let before = mean_error(&left(&image.pixels), 0.8);
+        let after = mean_error(&left(&denoised.pixels), 0.8);
+        assert!(after < before * 0.5, "{} -> {}", before, after);
+
+        // the right half (no green) didn't bleed into the left one
+        for row in 0..height {
+            let edge = &denoised.pixels[(row * width + width / 2) as usize];
+            assert!(*edge.g() < 1e-3);
+            let edge = &denoised.pixels[(row * width + width / 2 - 1) as usize];
+            assert!(*edge.g() > 0.5);
+        }
+    }
+}
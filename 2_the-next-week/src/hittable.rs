@@ -1,10 +1,13 @@
 use std::array;
+use std::f64::consts::PI;
+use std::fmt;
 
 use crate::aabb::AABB;
 use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::vec::Vector;
+use crate::util;
+use crate::vec::{self, Vector};
 
 type Vec3 = Vector<f64, 3>;
 type Vec2 = Vector<f64, 2>;
@@ -40,12 +43,42 @@ pub struct HitResult<'a> {
     pub material: Option<&'a dyn Material>,
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult>;
+    // `hit` that also adds the bvh nodes visited on the way to `visits`, only used for the stats
+    // (see `RayTracer::average_bvh_visits`)
+    fn hit_counted(
+        &self,
+        ray: Ray3,
+        t_range: Interval,
+        _visits: &mut usize,
+    ) -> Option<HitResult<'_>> {
+        self.hit(ray, t_range)
+    }
     fn get_material<'a>(&'a self) -> Option<&'a dyn Material> {
         None
     }
     fn bounding_box(&self) -> &AABB3;
+
+    // Density (over solid angle) of the directions from `origin` returned by `random_direction`,
+    // used to sample the lights. Zero for the directions missing the object and for the objects
+    // that can't be sampled.
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f64 {
+        0.0
+    }
+    // random direction from `origin` towards a point of the object
+    fn random_direction(&self, _origin: Vec3) -> Vec3 {
+        Vec3::new([1.0, 0.0, 0.0])
+    }
+}
+
+// the ray starting at `origin` going to `direction`, used to find where a sampled direction hits
+fn probe_ray(origin: Vec3, direction: Vec3) -> Ray3 {
+    Ray3 {
+        origin,
+        direction,
+        time: 0.0,
+    }
 }
 
 pub struct Sphere {
@@ -104,6 +137,17 @@ impl Sphere {
     fn sphere_center(&self, time: f64) -> Vec3 {
         self.center + self.center_vec * time
     }
+
+    // maps a point on the unit sphere to (longitude, latitude) normalized to [0, 1], u starts at
+    // -x going around the y axis and v goes from -y to +y
+    pub fn sphere_uv(point: Vec3) -> Vec2 {
+        use std::f64::consts::PI;
+
+        let theta = (-point[1]).clamp(-1.0, 1.0).acos();
+        let phi = (-point[2]).atan2(point[0]) + PI;
+
+        Vec2::new([phi / (2.0 * PI), theta / PI])
+    }
 }
 
 impl Hittable for Sphere {
@@ -139,10 +183,11 @@ impl Hittable for Sphere {
         };
 
         let point = ray.at(root);
-        let out_normal = (point - self.center) / self.radius;
+        let out_normal = (point - center) / self.radius;
+        let tex = Self::sphere_uv(out_normal);
 
         Some(HitResult {
-            record: HitRecord::new(ray, out_normal, point, Vec2::default(), root),
+            record: HitRecord::new(ray, out_normal, point, tex, root),
             material: self.get_material(),
         })
     }
@@ -150,6 +195,57 @@ impl Hittable for Sphere {
     fn bounding_box(&self) -> &AABB3 {
         &self.bbox
     }
+
+    // uniform over the cone of directions seen by the sphere, moving spheres are sampled at their
+    // starting position
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        if self
+            .hit(
+                probe_ray(origin, direction),
+                Interval::new(0.001, f64::INFINITY),
+            )
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => 1.0 / (2.0 * PI * (1.0 - cos_theta_max)),
+            None => 1.0 / (4.0 * PI),
+        }
+    }
+
+    fn random_direction(&self, origin: Vec3) -> Vec3 {
+        let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+            return vec::random_unit_vector();
+        };
+
+        let z = 1.0 + util::get_random_canonical() * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * util::get_random_canonical();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+
+        let w = (self.center - origin).unit_vector();
+        let a = match w[0].abs() > 0.9 {
+            true => Vec3::new([0.0, 1.0, 0.0]),
+            false => Vec3::new([1.0, 0.0, 0.0]),
+        };
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+
+        u * (r * phi.cos()) + v * (r * phi.sin()) + w * z
+    }
+}
+
+impl Sphere {
+    // cosine of the half angle of the cone around the sphere seen from `origin`, none if `origin`
+    // is inside the sphere (every direction hits it then)
+    fn cos_theta_max(&self, origin: Vec3) -> Option<f64> {
+        let distance_squared = (self.center - origin).length_squared();
+        match distance_squared > self.radius * self.radius {
+            true => Some((1.0 - self.radius * self.radius / distance_squared).sqrt()),
+            false => None,
+        }
+    }
 }
 
 impl Default for Sphere {
@@ -165,13 +261,242 @@ impl Default for Sphere {
     }
 }
 
+// A parallelogram spanned by the edges `u` and `v` starting from the corner `q`
+pub struct Quad {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Option<Box<dyn Material>>,
+    bbox: AABB3,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: Option<Box<dyn Material>>) -> Self {
+        // a quad is flat, so the box is padded to be usable inside the bvh
+        let bbox_diagonal1 = AABB3::from_points(q, q + u + v);
+        let bbox_diagonal2 = AABB3::from_points(q + u, q + v);
+        let bbox = bbox_diagonal1
+            .combine_new(&bbox_diagonal2)
+            .pad_to_minimums(0.0001);
+
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+
+        Self {
+            q,
+            u,
+            v,
+            material,
+            bbox,
+            normal,
+            d: normal.dot(q),
+            w: n / n.dot(n),
+            area: n.length(),
+        }
+    }
+
+    // returns the planar coordinates of the point if it lies inside the quad
+    fn planar_coordinates(&self, point: Vec3) -> Option<Vec2> {
+        let planar_hit = point - self.q;
+        let alpha = self.w.dot(planar_hit.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hit));
+
+        let unit = Interval::new(0.0, 1.0);
+        match unit.contains(alpha) && unit.contains(beta) {
+            true => Some(Vec2::new([alpha, beta])),
+            false => None,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn get_material(&self) -> Option<&dyn Material> {
+        self.material.as_deref()
+    }
+
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        let denom = self.normal.dot(ray.direction);
+
+        // ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(ray.origin)) / denom;
+        if !t_range.contains(t) {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let tex = self.planar_coordinates(point)?;
+
+        Some(HitResult {
+            record: HitRecord::new(ray, self.normal, point, tex, t),
+            material: self.get_material(),
+        })
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        &self.bbox
+    }
+
+    // uniform over the area, converted to solid angle
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let Some(hit) = self.hit(
+            probe_ray(origin, direction),
+            Interval::new(0.001, f64::INFINITY),
+        ) else {
+            return 0.0;
+        };
+
+        let distance_squared = hit.record.t_value * hit.record.t_value * direction.length_squared();
+        let cosine = direction.dot(self.normal).abs() / direction.length();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random_direction(&self, origin: Vec3) -> Vec3 {
+        let point =
+            self.q + self.u * util::get_random_canonical() + self.v * util::get_random_canonical();
+        point - origin
+    }
+}
+
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[Vec2; 3]>,
+    pub material: Option<Box<dyn Material>>,
+    bbox: AABB3,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vec3; 3], material: Option<Box<dyn Material>>) -> Self {
+        Self::with_attributes(vertices, None, None, material)
+    }
+
+    // normals and uvs are per-vertex and get interpolated using the barycentric coordinates
+    pub fn with_attributes(
+        vertices: [Vec3; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[Vec2; 3]>,
+        material: Option<Box<dyn Material>>,
+    ) -> Self {
+        Self {
+            vertices,
+            normals,
+            uvs,
+            material,
+            bbox: Self::bounding_box_of(&vertices),
+        }
+    }
+
+    pub(crate) fn bounding_box_of(vertices: &[Vec3; 3]) -> AABB3 {
+        let [a, b, c] = vertices;
+        AABB3::from_points(*a, *b)
+            .combine_new(&AABB3::from_points(*a, *c))
+            .pad_to_minimums(0.0001)
+    }
+
+    // Möller–Trumbore intersection, shared with the triangles of a mesh
+    pub(crate) fn intersect(
+        ray: &Ray3,
+        t_range: &Interval,
+        vertices: &[Vec3; 3],
+        normals: Option<&[Vec3; 3]>,
+        uvs: Option<&[Vec2; 3]>,
+    ) -> Option<HitRecord> {
+        const EPSILON: f64 = 1e-12;
+
+        let [p0, p1, p2] = *vertices;
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+
+        let pvec = ray.direction.cross(edge2);
+        let det = edge1.dot(pvec);
+
+        // ray is parallel to the triangle
+        if det.abs() < EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = ray.origin - p0;
+        let b1 = tvec.dot(pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(edge1);
+        let b2 = ray.direction.dot(qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(qvec) * inv_det;
+        if !t_range.surrounds(t) {
+            return None;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        let tex = match uvs {
+            Some([uv0, uv1, uv2]) => *uv0 * b0 + *uv1 * b1 + *uv2 * b2,
+            None => Vec2::new([b1, b2]),
+        };
+
+        let geometric_normal = edge1.cross(edge2).unit_vector();
+        let mut record = HitRecord::new(ray.clone(), geometric_normal, ray.at(t), tex, t);
+
+        // shading normal follows the side of the geometric normal that was hit
+        if let Some([n0, n1, n2]) = normals {
+            let shading_normal = (*n0 * b0 + *n1 * b1 + *n2 * b2).unit_vector();
+            let shading_normal = match shading_normal.dot(geometric_normal) < 0.0 {
+                true => -shading_normal,
+                false => shading_normal,
+            };
+            record.normal = match record.front_face {
+                true => shading_normal,
+                false => -shading_normal,
+            };
+        }
+
+        Some(record)
+    }
+}
+
+impl Hittable for Triangle {
+    fn get_material(&self) -> Option<&dyn Material> {
+        self.material.as_deref()
+    }
+
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        let record = Self::intersect(
+            &ray,
+            &t_range,
+            &self.vertices,
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+        )?;
+
+        Some(HitResult {
+            record,
+            material: self.get_material(),
+        })
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        &self.bbox
+    }
+}
+
 pub struct HittableList {
     objects: Vec<Box<dyn Hittable>>,
     bbox: AABB3,
 }
 
-unsafe impl Sync for HittableList {}
-
 impl Hittable for HittableList {
     fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult> {
         let mut current_hit = None;
@@ -187,9 +512,57 @@ impl Hittable for HittableList {
         current_hit
     }
 
+    fn hit_counted(
+        &self,
+        ray: Ray3,
+        t_range: Interval,
+        visits: &mut usize,
+    ) -> Option<HitResult<'_>> {
+        let mut current_hit = None;
+        let mut t_closest = t_range.max;
+
+        for object in self.objects.iter() {
+            let t_range = (t_range.min, t_closest).into();
+            if let Some(hit) = object.hit_counted(ray.clone(), t_range, visits) {
+                t_closest = hit.record.t_value;
+                current_hit = Some(hit);
+            }
+        }
+
+        current_hit
+    }
+
     fn bounding_box(&self) -> &AABB3 {
         &self.bbox
     }
+
+    // every object is picked with the same probability
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let sum = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum::<f64>();
+        sum / self.objects.len() as f64
+    }
+
+    fn random_direction(&self, origin: Vec3) -> Vec3 {
+        let index = util::get_random(0, self.objects.len());
+        self.objects[index].random_direction(origin)
+    }
+}
+
+// the objects can't be printed, only their count
+impl fmt::Debug for HittableList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HittableList")
+            .field("objects", &self.objects.len())
+            .finish()
+    }
 }
 
 impl HittableList {
@@ -204,4 +577,157 @@ impl HittableList {
         self.bbox.combine(object.bounding_box());
         self.objects.push(object);
     }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+// Axis-aligned box with two opposite vertices `a` and `b`, made of 6 quads. A material is created
+// for each side.
+pub fn make_box<F>(a: Vec3, b: Vec3, material: F) -> HittableList
+where
+    F: Fn() -> Box<dyn Material>,
+{
+    let mut sides = HittableList::new();
+
+    let min = Vec3::new(array::from_fn(|i| a[i].min(b[i])));
+    let max = Vec3::new(array::from_fn(|i| a[i].max(b[i])));
+
+    let dx = Vec3::new([max[0] - min[0], 0.0, 0.0]);
+    let dy = Vec3::new([0.0, max[1] - min[1], 0.0]);
+    let dz = Vec3::new([0.0, 0.0, max[2] - min[2]]);
+
+    let [x0, y0, z0] = min.data;
+    let [x1, y1, z1] = max.data;
+
+    let sides_def = [
+        (Vec3::new([x0, y0, z1]), dx, dy),  // front
+        (Vec3::new([x1, y0, z1]), -dz, dy), // right
+        (Vec3::new([x1, y0, z0]), -dx, dy), // back
+        (Vec3::new([x0, y0, z0]), dz, dy),  // left
+        (Vec3::new([x0, y1, z1]), dx, -dz), // top
+        (Vec3::new([x0, y0, z0]), dx, dz),  // bottom
+    ];
+
+    for (q, u, v) in sides_def {
+        sides.add(Box::new(Quad::new(q, u, v, Some(material()))));
+    }
+
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sphere_uv() {
+        let cases = [
+            ([1.0, 0.0, 0.0], [0.5, 0.5]),
+            ([-1.0, 0.0, 0.0], [0.0, 0.5]),
+            ([0.0, 1.0, 0.0], [0.5, 1.0]),
+            ([0.0, -1.0, 0.0], [0.5, 0.0]),
+            ([0.0, 0.0, 1.0], [0.25, 0.5]),
+            ([0.0, 0.0, -1.0], [0.75, 0.5]),
+        ];
+
+        for (point, expected) in cases {
+            let uv = Sphere::sphere_uv(point.into());
+            assert!(
+                (uv - Vec2::from(expected)).near_zero(),
+                "{:?}: {}",
+                point,
+                uv
+            );
+        }
+    }
+
+    #[test]
+    fn test_moving_sphere_uv() {
+        let sphere = Sphere::new_moving([0.0, 0.0, 0.0].into(), [10.0, 0.0, 0.0].into(), 1.0, None);
+        let ray = Ray {
+            origin: [5.0, 5.0, 0.0].into(),
+            direction: [0.0, -1.0, 0.0].into(),
+            time: 0.5,
+        };
+
+        let record = sphere
+            .hit(ray, Interval::new(0.0, f64::INFINITY))
+            .unwrap()
+            .record;
+        assert!((record.normal - Vec3::from([0.0, 1.0, 0.0])).near_zero());
+        assert!((record.tex - Vec2::from([0.5, 1.0])).near_zero());
+    }
+
+    #[test]
+    fn test_quad() {
+        // flat on the z axis
+        let quad = Quad::new(
+            [1.0, 2.0, 3.0].into(),
+            [2.0, 0.0, 0.0].into(),
+            [0.0, 4.0, 0.0].into(),
+            None,
+        );
+        let ray = |x, y| Ray {
+            origin: [x, y, 10.0].into(),
+            direction: [0.0, 0.0, -1.0].into(),
+            time: 0.0,
+        };
+        let t_range = Interval::new(0.001, f64::INFINITY);
+
+        // the planar coordinates go from 0 to 1 along the edges
+        let record = quad.hit(ray(1.5, 5.0), t_range.clone()).unwrap().record;
+        assert!((record.point - Vec3::from([1.5, 5.0, 3.0])).near_zero());
+        assert!((record.tex - Vec2::from([0.25, 0.75])).near_zero());
+        assert_eq!(record.t_value, 7.0);
+
+        assert!(quad.hit(ray(0.5, 5.0), t_range.clone()).is_none());
+        assert!(quad.hit(ray(1.5, 6.5), t_range).is_none());
+
+        let bbox = quad.bounding_box();
+        assert_eq!(bbox.axis_interval(0).size(), 2.0);
+        assert_eq!(bbox.axis_interval(1).size(), 4.0);
+        let z = bbox.axis_interval(2);
+        assert!(z.size() > 0.0 && z.contains(3.0));
+    }
+
+    #[test]
+    fn test_light_pdf() {
+        util::seed_rng(5);
+        let origin = Vec3::new([0.0, 0.0, 0.0]);
+        let lights: [Box<dyn Hittable>; 3] = [
+            Box::new(Quad::new(
+                [-1.0, 1.0, -1.0].into(),
+                [2.0, 0.0, 0.0].into(),
+                [0.0, 0.0, 2.0].into(),
+                None,
+            )),
+            Box::new(Sphere::new([0.0, 0.0, -3.0].into(), 2.0, None)),
+            // the origin is inside
+            Box::new(Sphere::new([0.5, 0.0, 0.0].into(), 2.0, None)),
+        ];
+
+        for light in lights {
+            // the sampled directions hit the light
+            for _ in 0..100 {
+                let direction = light.random_direction(origin);
+                assert!(light.pdf_value(origin, direction) > 0.0);
+            }
+
+            // the density integrates to 1 over the sphere of directions
+            let count = 20000;
+            let integral = (0..count)
+                .map(|_| light.pdf_value(origin, vec::random_unit_vector()))
+                .sum::<f64>()
+                * 4.0
+                * PI
+                / count as f64;
+            assert!((integral - 1.0).abs() < 0.05, "{}", integral);
+        }
+    }
 }
@@ -1,29 +1,43 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::fs::File;
-use std::io::Write;
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::usize;
 
 pub mod aabb;
+pub mod aov;
 pub mod bvh;
+pub mod checkpoint;
 pub mod color;
+pub mod denoise;
 pub mod hittable;
 pub mod interval;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod noise;
+pub mod obj;
+pub mod output;
 pub mod progress_tracker;
 pub mod ray;
 pub mod ray_tracer;
 pub mod scenes;
 pub mod texture;
+pub mod tonemap;
+pub mod transform;
 pub mod util;
 pub mod vec;
 
+use aov::Aov;
+use bvh::SplitMethod;
 use clap::{arg, value_parser, Arg, ArgAction, Command};
-use color::Color;
 use config::Config;
+use output::OutputFormat;
 use rand::seq::SliceRandom;
-use ray_tracer::Image;
+use ray_tracer::{AdaptiveSampling, Image, ProgressiveParams, RayTracer};
+use scenes::{Scene, SceneOptions};
+use tonemap::{Operator, ToneMapping, Transfer};
 use vec::Vector;
 
 use self::hittable::HittableList;
@@ -56,13 +70,27 @@ macro_rules! parse_config_fn {
 pub struct ParsedArgs {
     pub tracer_params: TracerParams,
     pub scene: HittableList,
+    pub scene_name: String,
+    pub scene_options: SceneOptions,
     pub output: PathBuf,
+    pub output_format: OutputFormat,
+    pub tone_mapping: ToneMapping,
+    pub aovs: Vec<Aov>,
+    pub denoise: bool,
+    pub heatmap: bool,
+    pub progressive: Option<ProgressiveParams>,
+    pub checkpoint: Option<PathBuf>,
+    pub resume: Option<PathBuf>,
     pub use_single_thread: bool,
     pub force_output: bool,
+    pub bvh_stats: bool,
 }
 
 pub fn parse_args() -> ParsedArgs {
     let mut param = TracerParams::default();
+    let mut scene_options = SceneOptions::default();
+    let mut tone_mapping = ToneMapping::default();
+    let mut aovs = Vec::new();
 
     let scene_list = scenes::SCENES
         .iter()
@@ -74,6 +102,18 @@ pub fn parse_args() -> ParsedArgs {
         .version("2.0")
         .about("A ray tracer")
         .arg(arg!([output] "Optional output file (default: 'image.ppm')"))
+        .arg(arg!(--format <FMT> "Output format, guessed from the output extension if omitted (FMT: \"ppm\", \"ppm-ascii\", \"png\", \"png16\", \"hdr\", \"pfm\", \"exr\" or \"exr-zip\")"))
+        .arg(
+            arg!(--exposure <FLOAT> "Exposure compensation in stops (default: 0)")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(arg!(--tonemap <OPERATOR> "Tone mapping operator (OPERATOR: \"clamp\", \"reinhard\", \"reinhard-extended\", \"hable\" or \"aces\")"))
+        .arg(
+            arg!(--white_point <FLOAT> "Luminance that becomes white with \"reinhard-extended\"")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(arg!(--transfer <FUNCTION> "Transfer function (FUNCTION: \"srgb\", \"gamma2\" or \"linear\")"))
+        .arg(arg!(--aovs <LIST> "Auxiliary buffers written next to the output, comma separated or \"all\" (LIST: \"albedo\", \"normal\", \"depth\", \"position\", \"id\")"))
         .arg(arg!(-g --config <FILE> "Config file (default: 'renderconfig.toml')"))
         .arg(arg!(-t --height <INT> "Height").value_parser(value_parser!(u32)))
         .arg(arg!(-s --sampling <INT> "Sampling rate").value_parser(value_parser!(u32)))
@@ -91,6 +131,48 @@ pub fn parse_args() -> ParsedArgs {
                 .action(ArgAction::SetTrue),
         )
         .arg(arg!(--force "Overwrite output if exists"))
+        .arg(arg!(--denoise "Filter the noise of the image, guided by the albedo, normal and depth buffers"))
+        .arg(arg!(--bvh <METHOD> "BVH split method (METHOD: \"median\" (default) or \"sah\")"))
+        .arg(
+            arg!(--leaf_size <INT> "Maximum number of objects in a BVH leaf (default: 1)")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--seed <INT> "Seed for the scene generation and the sampling (default: 0)")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--adaptive_threshold <FLOAT> "Enable adaptive sampling, a pixel stops when its relative error is below the threshold (e.g. 0.01)")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--min_samples <INT> "Samples taken before a pixel may stop with adaptive sampling (default: 16)")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            arg!(--progressive <INT> "Render in passes of INT samples per pixel and write snapshots of the image while rendering")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            arg!(--snapshot_interval <SECONDS> "Minimum time between two snapshots in progressive mode (default: 30)")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("time_limit")
+                .long("time-limit")
+                .value_name("SECONDS")
+                .help("Keep adding samples to the whole image until the time is up, the sampling rate is ignored (implies progressive mode)")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(arg!(--checkpoint <FILE> "Checkpoint file saved with every snapshot, implies progressive mode (default in progressive mode: output with the extension 'ckpt')"))
+        .arg(arg!(--resume <FILE> "Continue a progressive render from its checkpoint, the parameters must be the same"))
+        .arg(arg!(--heatmap "Write the number of samples taken per pixel next to the output"))
+        .arg(arg!(--no_light_sampling "Only find the lights by following the scattered rays instead of also sampling them directly"))
+        .arg(
+            arg!(--tile_size <INT> "Tile size in pixels for multi-threaded rendering")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(arg!(--bvh_stats "Compare the BVH split methods on the scene instead of rendering"))
         .arg(
             Arg::new("scene")
                 .short('i')
@@ -129,14 +211,122 @@ pub fn parse_args() -> ParsedArgs {
     parse_config!(config, matches, "focus", f64, param.focus_distance);
     parse_config_fn!(config, matches, "look_from", parse_vector, param.look_from);
     parse_config_fn!(config, matches, "look_at", parse_vector, param.look_at);
+    parse_config!(config, matches, "tile_size", u32, param.tile_size);
+    let mut adaptive = AdaptiveSampling::default();
+    let mut adaptive_threshold = 0.0;
+    parse_config!(
+        config,
+        matches,
+        "adaptive_threshold",
+        f64,
+        adaptive_threshold
+    );
+    parse_config!(config, matches, "min_samples", u32, adaptive.min_samples);
+    if adaptive_threshold > 0.0 {
+        adaptive.threshold = adaptive_threshold;
+        param.adaptive = Some(adaptive);
+    }
+    parse_config!(config, matches, "exposure", f64, tone_mapping.exposure);
+    parse_config!(
+        config,
+        matches,
+        "white_point",
+        f64,
+        tone_mapping.white_point
+    );
+    parse_config_fn!(
+        config,
+        matches,
+        "tonemap",
+        |v: &str| v.parse::<Operator>().ok(),
+        tone_mapping.operator
+    );
+    parse_config_fn!(
+        config,
+        matches,
+        "transfer",
+        |v: &str| v.parse::<Transfer>().ok(),
+        tone_mapping.transfer
+    );
+    parse_config_fn!(
+        config,
+        matches,
+        "aovs",
+        |v: &str| Aov::parse_list(v)
+            .map_err(|e| eprintln!("{}. No aovs will be written", e))
+            .ok(),
+        aovs
+    );
+    let mut samples_per_pass = 0;
+    let mut snapshot_interval = ProgressiveParams::default().snapshot_interval.as_secs_f64();
+    parse_config!(config, matches, "progressive", u32, samples_per_pass);
+    parse_config!(config, matches, "snapshot_interval", f64, snapshot_interval);
+    let mut time_limit = 0.0;
+    parse_config!(config, matches, "time_limit", f64, time_limit);
+    let time_limit = (time_limit > 0.0).then(|| Duration::from_secs_f64(time_limit));
+
+    let resume = matches.get_one::<String>("resume").map(PathBuf::from);
+    let checkpoint = matches.get_one::<String>("checkpoint").map(PathBuf::from);
+    // resuming, checkpointed and time limited renders are always progressive
+    let implied = resume.is_some() || checkpoint.is_some() || time_limit.is_some();
+    if implied && samples_per_pass == 0 {
+        samples_per_pass = ProgressiveParams::default().samples_per_pass;
+    }
+    let progressive = (samples_per_pass > 0).then(|| ProgressiveParams {
+        samples_per_pass,
+        snapshot_interval: Duration::from_secs_f64(snapshot_interval.max(0.0)),
+        time_limit,
+    });
+
+    let denoise = matches.get_flag("denoise");
+    let heatmap = matches.get_flag("heatmap");
+    // the denoiser needs the buffers even if they are not written
+    param.render_aovs = !aovs.is_empty() || denoise;
+    parse_config!(config, matches, "seed", u64, param.seed);
+    scene_options.seed = param.seed;
+    parse_config!(
+        config,
+        matches,
+        "leaf_size",
+        usize,
+        scene_options.bvh.max_leaf_size
+    );
+    parse_config_fn!(
+        config,
+        matches,
+        "bvh",
+        |v: &str| v
+            .parse::<SplitMethod>()
+            .map_err(|e| eprintln!("{}. Using {} instead", e, scene_options.bvh.split_method))
+            .ok(),
+        scene_options.bvh.split_method
+    );
 
     let output = matches
         .get_one::<String>("output")
         .map(|s| s.as_str())
         .unwrap_or("image.ppm");
 
+    let checkpoint = checkpoint.or_else(|| {
+        progressive
+            .as_ref()
+            .map(|_| Path::new(output).with_extension("ckpt"))
+    });
+
+    let output_format = match matches.get_one::<String>("format") {
+        Some(format) => format.parse::<OutputFormat>().unwrap_or_else(|e| {
+            eprintln!("{}. Using ppm instead", e);
+            OutputFormat::Ppm
+        }),
+        None => OutputFormat::from_extension(Path::new(output)).unwrap_or_else(|| {
+            eprintln!("Unknown output extension, writing as ppm (see --format)");
+            OutputFormat::Ppm
+        }),
+    };
+
     let use_single_thread = matches.get_flag("single-thread");
     let force_output = matches.get_flag("force");
+    let bvh_stats = matches.get_flag("bvh_stats");
 
     let get_random_scene = || {
         *scenes::SCENES
@@ -166,16 +356,67 @@ pub fn parse_args() -> ParsedArgs {
         }
     };
 
+    let Scene {
+        world,
+        background,
+        lights,
+    } = scenes::SCENES[scene_name](&scene_options);
+    param.background = background;
+    if !matches.get_flag("no_light_sampling") {
+        param.lights = lights;
+    }
+
     ParsedArgs {
         tracer_params: param,
         output: output.into(),
+        output_format,
+        tone_mapping,
+        aovs,
+        denoise,
+        heatmap,
+        progressive,
+        checkpoint,
+        resume,
         use_single_thread,
         force_output,
-        scene: scenes::SCENES[scene_name](),
+        bvh_stats,
+        scene: world,
+        scene_name: scene_name.to_string(),
+        scene_options,
+    }
+}
+
+// builds the scene with each split method and reports the build time and the number of bvh nodes
+// visited by the camera rays
+pub fn print_bvh_stats(scene_name: &str, options: &SceneOptions, ray_tracer: &RayTracer) {
+    eprintln!(
+        "BVH stats for scene '{}' (max leaf size: {})",
+        scene_name, options.bvh.max_leaf_size
+    );
+
+    for split_method in [SplitMethod::Median, SplitMethod::Sah] {
+        let mut options = options.clone();
+        options.bvh.split_method = split_method;
+
+        let now = Instant::now();
+        let scene = scenes::SCENES[scene_name](&options);
+        let build_time = now.elapsed();
+
+        let now = Instant::now();
+        let visits = ray_tracer.average_bvh_visits(&scene.world);
+        let trace_time = now.elapsed();
+
+        eprintln!(
+            "  {:<6} | build: {:>8.2}ms | trace: {:>8.2}ms | node visits per ray: {:>8.2}",
+            split_method.to_string(),
+            build_time.as_secs_f64() * 1000.0,
+            trace_time.as_secs_f64() * 1000.0,
+            visits
+        );
     }
 }
 
-pub fn generate_ppm_image(image: Image, path: &Path) {
+pub fn generate_image(image: Image, path: &Path, format: OutputFormat, tone_mapping: &ToneMapping) {
     if path.exists() && path.is_dir() {
         panic!("File exists and is a directory! Aborting");
     }
@@ -187,38 +428,92 @@ pub fn generate_ppm_image(image: Image, path: &Path) {
         );
     }
 
-    let mut file = File::create(path).expect(
-        format!(
-            "Failed to open file {}",
-            path.to_str().unwrap_or("{unknwon}")
+    output::write_image(&image, path, format, tone_mapping).unwrap_or_else(|e| {
+        panic!(
+            "Failed to write file {}: {}",
+            path.to_str().unwrap_or("{unknown}"),
+            e
         )
-        .as_str(),
-    );
-    let Image { pixels, dimension } = image;
+    });
+}
 
-    const MAX_COLOR: i32 = 255;
-    let mut temp = format!(
-        "P3\n{} {}\n{}\n",
-        dimension.width, dimension.height, MAX_COLOR
-    );
+// writes each aov to its own file next to the output (see `Aov::path`), in the output format. The
+// ldr formats get values remapped for viewing, the hdr formats keep the raw values.
+pub fn generate_aov_images(image: &Image, aovs: &[Aov], output: &Path, format: OutputFormat) {
+    let Some(buffers) = &image.aovs else {
+        return;
+    };
 
-    for (i, pixel) in pixels.iter().enumerate() {
-        let color: Color<i32> = pixel
-            .correct_gamma()
-            .clamp((0.0, 0.999).into())
-            .transform(|v| (v * MAX_COLOR as f64) as i32);
+    for &aov in aovs {
+        let display = format.max_value().is_some();
+        let tone_mapping = ToneMapping {
+            transfer: match aov {
+                Aov::Albedo => Transfer::Srgb,
+                _ => Transfer::Linear,
+            },
+            ..Default::default()
+        };
+
+        let path = aov.path(output);
+        eprintln!("Writing {} to {}", aov, path.display());
+        output::write_image(&buffers.image(aov, display), &path, format, &tone_mapping)
+            .unwrap_or_else(|e| panic!("Failed to write file {}: {}", path.display(), e));
+    }
+}
 
-        let line = format!("{} {} {}\n", color.r(), color.g(), color.b());
-        temp += &line;
+// Writes the current estimate of a progressive render to the output. The image is written to a
+// temporary file first and then renamed, so the output is never half written if the render is
+// killed in the middle.
+pub fn write_snapshot(
+    image: &Image,
+    path: &Path,
+    format: OutputFormat,
+    tone_mapping: &ToneMapping,
+) {
+    let partial_path = output::sibling_path(path, "partial");
+    let result = output::write_image(image, &partial_path, format, tone_mapping)
+        .map_err(|e| e.to_string())
+        .and_then(|_| fs::rename(&partial_path, path).map_err(|e| e.to_string()));
 
-        if (i + 1) % dimension.width as usize == 0 {
-            file.write_all(temp.as_bytes())
-                .expect("Failed to write to file");
-            temp.clear();
-        }
+    // a failed snapshot is not a reason to stop the render
+    match result {
+        Ok(_) => eprintln!("\nSnapshot written to {}", path.display()),
+        Err(e) => eprintln!("\nFailed to write snapshot {}: {}", path.display(), e),
     }
 }
 
+pub fn print_sample_report(image: &Image) {
+    let Some(counts) = image.sample_counts.as_ref().filter(|c| !c.is_empty()) else {
+        return;
+    };
+
+    let total = counts.iter().map(|&c| c as u64).sum::<u64>();
+    eprintln!(
+        "Samples per pixel: {:.2} on average (min: {}, max: {})",
+        total as f64 / counts.len() as f64,
+        counts.iter().min().unwrap(),
+        counts.iter().max().unwrap()
+    );
+}
+
+// writes the samples per pixel next to the output, as a heatmap in the ldr formats and as the raw
+// counts in the hdr formats
+pub fn generate_heatmap(image: &Image, output: &Path, format: OutputFormat) {
+    let display = format.max_value().is_some();
+    let Some(heatmap) = image.sample_heatmap(display) else {
+        return;
+    };
+    let tone_mapping = ToneMapping {
+        transfer: Transfer::Linear,
+        ..Default::default()
+    };
+
+    let path = output::sibling_path(output, "samples");
+    eprintln!("Writing sample heatmap to {}", path.display());
+    output::write_image(&heatmap, &path, format, &tone_mapping)
+        .unwrap_or_else(|e| panic!("Failed to write file {}: {}", path.display(), e));
+}
+
 fn parse_vector<T, const N: usize>(string: &str) -> Option<Vector<T, N>>
 where
     T: VecElement + std::str::FromStr + Debug,
@@ -2,7 +2,9 @@ use std::io::Read;
 use std::time::Instant;
 
 use ray_tracing_the_next_week as rtr;
-use rtr::ray_tracer::RayTracer;
+use rtr::checkpoint;
+use rtr::denoise::DenoiseParams;
+use rtr::ray_tracer::{Accumulator, RayTracer};
 use rtr::ParsedArgs;
 
 macro_rules! timeit {
@@ -19,12 +21,39 @@ fn main() {
         tracer_params,
         scene,
         output,
+        output_format,
+        tone_mapping,
+        aovs,
+        denoise,
+        heatmap,
+        progressive,
+        checkpoint,
+        resume,
+        scene_name,
+        scene_options,
         use_single_thread,
         force_output,
+        bvh_stats,
     } = rtr::parse_args();
     eprintln!("\n{:#?}\n", tracer_params);
 
-    if output.exists() {
+    if bvh_stats {
+        let ray_tracer = RayTracer::new(tracer_params);
+        rtr::print_bvh_stats(&scene_name, &scene_options, &ray_tracer);
+        return;
+    }
+
+    let description = checkpoint::describe(&tracer_params, &scene_name, &scene_options);
+    let resume = resume.map(|path| {
+        eprintln!("Resuming from checkpoint {}", path.display());
+        checkpoint::load(&path, &description).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    });
+
+    // the output of the interrupted render is expected to be there
+    if output.exists() && resume.is_none() {
         eprintln!("File already exist! ({})", output.display());
 
         if !force_output {
@@ -43,11 +72,41 @@ fn main() {
 
     let ray_tracer = RayTracer::new(tracer_params);
 
-    let (image, duration) = timeit!(match use_single_thread {
-        true => ray_tracer.render(&scene),
-        false => ray_tracer.render_multi(&scene),
+    let (mut image, duration) = timeit!(match (&progressive, use_single_thread) {
+        (Some(params), _) => {
+            let save_checkpoint = |accumulator: &Accumulator| {
+                if let Some(path) = &checkpoint {
+                    checkpoint::save(path, &description, accumulator).unwrap_or_else(|e| {
+                        eprintln!("\nFailed to save checkpoint {}: {}", path.display(), e)
+                    });
+                }
+            };
+            let image = ray_tracer.render_progressive(&scene, params, resume, |accumulator| {
+                save_checkpoint(accumulator);
+                rtr::write_snapshot(&accumulator.image(), &output, output_format, &tone_mapping)
+            });
+            image
+        }
+        (None, true) => ray_tracer.render(&scene),
+        (None, false) => ray_tracer.render_multi(&scene),
     });
     eprintln!("Rendering took {:.2} seconds", duration.as_secs_f64());
+    rtr::print_sample_report(&image);
+
+    if denoise {
+        let guides = image.aovs.as_ref().expect("Denoising needs the aovs");
+        let (denoised, duration) = timeit!(rtr::denoise::denoise(
+            &image,
+            guides,
+            &DenoiseParams::default()
+        ));
+        eprintln!("Denoising took {:.2} seconds", duration.as_secs_f64());
+        image = denoised;
+    }
 
-    rtr::generate_ppm_image(image, &output)
+    if heatmap {
+        rtr::generate_heatmap(&image, &output, output_format);
+    }
+    rtr::generate_aov_images(&image, &aovs, &output, output_format);
+    rtr::generate_image(image, &output, output_format, &tone_mapping)
 }
@@ -1,5 +1,8 @@
+use std::f64::consts::PI;
+
 use crate::color::Color;
 use crate::hittable::HitRecord;
+use crate::interval::Interval;
 use crate::ray::Ray;
 use crate::texture::{SolidColor, Texture};
 use crate::util;
@@ -13,8 +16,26 @@ pub struct ScatterResult {
     pub attenuation: Color,
 }
 
-pub trait Material {
+pub trait Material: Send + Sync {
     fn scatter(&self, ray: Ray3, hit_record: HitRecord) -> Option<ScatterResult>;
+
+    // light emitted by the material at the hit point, black for non-emissive materials
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        Color::new_one(0.0)
+    }
+
+    // base color of the surface at the hit point, used for the albedo aov
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::new_one(1.0)
+    }
+
+    // Density (over solid angle) of `scatter` choosing `direction`. The attenuation times this
+    // is the amount of light coming from `direction` that gets scattered, which is used to sample
+    // the lights directly. None for the specular materials, their directions can't be hit by
+    // sampling a light.
+    fn scattering_pdf(&self, _hit_record: &HitRecord, _direction: Vec3) -> Option<f64> {
+        None
+    }
 }
 
 // diffuse material
@@ -38,6 +59,16 @@ impl Material for Lambertian {
             attenuation: self.texture.value(hit_record.tex, hit_record.point),
         })
     }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.texture.value(hit_record.tex, hit_record.point)
+    }
+
+    // the scattered directions are cosine distributed
+    fn scattering_pdf(&self, hit_record: &HitRecord, direction: Vec3) -> Option<f64> {
+        let cosine = hit_record.normal.dot(direction.unit_vector());
+        Some(cosine.max(0.0) / PI)
+    }
 }
 
 impl Lambertian {
@@ -75,6 +106,10 @@ impl Material for Metal {
             _ => None,
         }
     }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        self.albedo.clone()
+    }
 }
 
 impl Metal {
@@ -131,3 +166,72 @@ impl Material for Dielectric {
         })
     }
 }
+
+// emissive material (area light), doesn't scatter any ray
+pub struct DiffuseLight {
+    pub texture: Box<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self {
+            texture: Box::new(SolidColor::new(emit)),
+        }
+    }
+
+    pub fn with_texture(texture: Box<dyn Texture>) -> Self {
+        Self { texture }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: Ray3, _hit_record: HitRecord) -> Option<ScatterResult> {
+        None
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        self.texture.value(hit_record.tex, hit_record.point)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.emitted(hit_record).clamp(Interval::new(0.0, 1.0))
+    }
+}
+
+// scatters uniformly in every direction, used as the phase function of participating media
+pub struct Isotropic {
+    pub texture: Box<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self {
+            texture: Box::new(SolidColor::new(albedo)),
+        }
+    }
+
+    pub fn with_texture(texture: Box<dyn Texture>) -> Self {
+        Self { texture }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, ray: Ray3, hit_record: HitRecord) -> Option<ScatterResult> {
+        Some(ScatterResult {
+            ray: Ray {
+                origin: hit_record.point,
+                direction: vec::random_unit_vector(),
+                time: ray.time,
+            },
+            attenuation: self.texture.value(hit_record.tex, hit_record.point),
+        })
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.texture.value(hit_record.tex, hit_record.point)
+    }
+
+    fn scattering_pdf(&self, _hit_record: &HitRecord, _direction: Vec3) -> Option<f64> {
+        Some(1.0 / (4.0 * PI))
+    }
+}
@@ -0,0 +1,129 @@
+use crate::aabb::AABB;
+use crate::color::Color;
+use crate::hittable::{HitRecord, HitResult, Hittable};
+use crate::interval::Interval;
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::util;
+use crate::vec::Vector;
+
+type Vec3 = Vector<f64, 3>;
+type Vec2 = Vector<f64, 2>;
+type Ray3 = Ray<f64, 3>;
+type AABB3 = AABB<f64, 3>;
+
+// A volume of constant density (smoke, fog, ...) filling a closed boundary. A ray going through
+// it can scatter at any point inside with a probability that depends on the density.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Box<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hittable>, density: f64, texture: Box<dyn Texture>) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Box::new(Isotropic::with_texture(texture)),
+        }
+    }
+
+    pub fn from_color(boundary: Box<dyn Hittable>, density: f64, albedo: Color) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Box::new(Isotropic::new(albedo)),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        // find where the ray enters and exits the boundary (the ray may start inside)
+        let enter = self.boundary.hit(ray.clone(), Interval::universe())?;
+        let exit_range = Interval::new(enter.record.t_value + 0.0001, f64::INFINITY);
+        let exit = self.boundary.hit(ray.clone(), exit_range)?;
+
+        let t_enter = enter.record.t_value.max(t_range.min).max(0.0);
+        let t_exit = exit.record.t_value.min(t_range.max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction.length();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * util::get_random_canonical().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t_value = t_enter + hit_distance / ray_length;
+
+        // normal and front_face are arbitrary, the phase function doesn't use them
+        let record = HitRecord {
+            point: ray.at(t_value),
+            normal: Vec3::new([1.0, 0.0, 0.0]),
+            tex: Vec2::default(),
+            t_value,
+            front_face: true,
+        };
+
+        Some(HitResult {
+            record,
+            material: self.get_material(),
+        })
+    }
+
+    fn get_material(&self) -> Option<&dyn Material> {
+        Some(self.phase_function.as_ref())
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::make_box;
+    use crate::material::Lambertian;
+
+    // a slab of thickness 1 along z
+    fn slab(density: f64) -> ConstantMedium {
+        let boundary = make_box([-5.0, -5.0, 0.0].into(), [5.0, 5.0, 1.0].into(), || {
+            Box::new(Lambertian::new(Color::new_one(0.5)))
+        });
+        ConstantMedium::from_color(Box::new(boundary), density, Color::new_one(0.5))
+    }
+
+    // fraction of the rays going through the slab that scatter inside it
+    fn scatter_frequency(medium: &ConstantMedium) -> f64 {
+        let count = 20000;
+        let ray = Ray {
+            origin: [0.0, 0.0, -2.0].into(),
+            direction: [0.0, 0.0, 1.0].into(),
+            time: 0.0,
+        };
+
+        let hits = (0..count)
+            .filter_map(|_| medium.hit(ray.clone(), Interval::new(0.001, f64::INFINITY)))
+            .inspect(|hit| assert!(hit.record.point[2] >= 0.0 && hit.record.point[2] <= 1.0))
+            .count();
+        hits as f64 / count as f64
+    }
+
+    #[test]
+    fn test_scatter_probability() {
+        util::seed_rng(6);
+
+        // the probability to go through a distance d without scattering is e^(-density * d)
+        let frequency = scatter_frequency(&slab(1.0));
+        let expected = 1.0 - (-1.0f64).exp();
+        assert!((frequency - expected).abs() < 0.02, "{}", frequency);
+
+        assert_eq!(scatter_frequency(&slab(0.0)), 0.0);
+    }
+}
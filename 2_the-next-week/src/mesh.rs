@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::bvh::BvhNode;
+use crate::hittable::{HitResult, Hittable, Triangle};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec::Vector;
+
+type Vec3 = Vector<f64, 3>;
+type Vec2 = Vector<f64, 2>;
+type Ray3 = Ray<f64, 3>;
+type AABB3 = AABB<f64, 3>;
+
+// Vertex and index buffers shared by every triangle of a mesh. `normals` and `uvs`, if present,
+// are indexed the same way as `positions`.
+pub struct MeshBuffers {
+    pub positions: Vec<Vec3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<Vec2>>,
+    pub indices: Vec<[usize; 3]>,
+    pub material: Option<Box<dyn Material>>,
+}
+
+impl MeshBuffers {
+    fn vertices(&self, face: usize) -> [Vec3; 3] {
+        self.indices[face].map(|i| self.positions[i])
+    }
+
+    fn normals(&self, face: usize) -> Option<[Vec3; 3]> {
+        let normals = self.normals.as_ref()?;
+        Some(self.indices[face].map(|i| normals[i]))
+    }
+
+    fn uvs(&self, face: usize) -> Option<[Vec2; 3]> {
+        let uvs = self.uvs.as_ref()?;
+        Some(self.indices[face].map(|i| uvs[i]))
+    }
+}
+
+// A single face of a mesh, only stores its index into the shared buffers
+struct MeshTriangle {
+    mesh: Arc<MeshBuffers>,
+    face: usize,
+    bbox: AABB3,
+}
+
+impl Hittable for MeshTriangle {
+    fn get_material(&self) -> Option<&dyn Material> {
+        self.mesh.material.as_deref()
+    }
+
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        let record = Triangle::intersect(
+            &ray,
+            &t_range,
+            &self.mesh.vertices(self.face),
+            self.mesh.normals(self.face).as_ref(),
+            self.mesh.uvs(self.face).as_ref(),
+        )?;
+
+        Some(HitResult {
+            record,
+            material: self.get_material(),
+        })
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        &self.bbox
+    }
+}
+
+// An indexed triangle mesh that acts as a single object, triangles are kept in its own bvh
+pub struct TriangleMesh {
+    buffers: Arc<MeshBuffers>,
+    bvh: BvhNode,
+}
+
+impl TriangleMesh {
+    pub fn new(buffers: MeshBuffers) -> Self {
+        let vertex_count = buffers.positions.len();
+        assert!(
+            buffers.indices.iter().flatten().all(|&i| i < vertex_count),
+            "Mesh index out of bounds"
+        );
+        if let Some(normals) = &buffers.normals {
+            assert_eq!(normals.len(), vertex_count, "Mesh normals count mismatch");
+        }
+        if let Some(uvs) = &buffers.uvs {
+            assert_eq!(uvs.len(), vertex_count, "Mesh uvs count mismatch");
+        }
+
+        let buffers = Arc::new(buffers);
+        let triangles = (0..buffers.indices.len())
+            .map(|face| {
+                Box::new(MeshTriangle {
+                    mesh: buffers.clone(),
+                    face,
+                    bbox: Triangle::bounding_box_of(&buffers.vertices(face)),
+                }) as Box<dyn Hittable>
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            bvh: BvhNode::new(triangles),
+            buffers,
+        }
+    }
+
+    pub fn buffers(&self) -> &MeshBuffers {
+        &self.buffers
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.buffers.indices.len()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        self.bvh.hit(ray, t_range)
+    }
+
+    fn hit_counted(
+        &self,
+        ray: Ray3,
+        t_range: Interval,
+        visits: &mut usize,
+    ) -> Option<HitResult<'_>> {
+        self.bvh.hit_counted(ray, t_range, visits)
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_square() -> MeshBuffers {
+        MeshBuffers {
+            positions: vec![
+                Vec3::new([0.0, 0.0, 0.0]),
+                Vec3::new([1.0, 0.0, 0.0]),
+                Vec3::new([1.0, 1.0, 0.0]),
+                Vec3::new([0.0, 1.0, 0.0]),
+            ],
+            normals: None,
+            uvs: Some(vec![
+                Vec2::new([0.0, 0.0]),
+                Vec2::new([1.0, 0.0]),
+                Vec2::new([1.0, 1.0]),
+                Vec2::new([0.0, 1.0]),
+            ]),
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            material: None,
+        }
+    }
+
+    fn ray_towards(x: f64, y: f64) -> Ray3 {
+        Ray {
+            origin: Vec3::new([x, y, 1.0]),
+            direction: Vec3::new([0.0, 0.0, -1.0]),
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_hit() {
+        let mesh = TriangleMesh::new(unit_square());
+        let range = Interval::new(0.001, f64::INFINITY);
+
+        for (x, y) in [(0.25, 0.75), (0.75, 0.25), (0.5, 0.5)] {
+            let record = mesh.hit(ray_towards(x, y), range.clone()).unwrap().record;
+            assert!((record.t_value - 1.0).abs() < 1e-9);
+            assert!((record.tex[0] - x).abs() < 1e-9);
+            assert!((record.tex[1] - y).abs() < 1e-9);
+            assert!(record.front_face);
+        }
+
+        assert!(mesh.hit(ray_towards(1.5, 0.5), range.clone()).is_none());
+        assert!(mesh.hit(ray_towards(-0.1, 0.5), range).is_none());
+    }
+
+    #[test]
+    fn test_interpolated_normal() {
+        let mut buffers = unit_square();
+        let tilted = Vec3::new([1.0, 0.0, 1.0]).unit_vector();
+        buffers.normals = Some(vec![tilted; 4]);
+
+        let mesh = TriangleMesh::new(buffers);
+        let record = mesh
+            .hit(ray_towards(0.5, 0.5), Interval::new(0.001, f64::INFINITY))
+            .unwrap()
+            .record;
+        assert!((record.normal - tilted).near_zero());
+    }
+}
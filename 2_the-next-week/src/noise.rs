@@ -0,0 +1,116 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::vec::Vector;
+
+type Vec3 = Vector<f64, 3>;
+
+const POINT_COUNT: usize = 256;
+
+// Perlin gradient noise, the same seed always gives the same noise
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                // uniformly distributed unit vectors
+                let v = Vec3::new([0; 3].map(|_| rng.gen_range(-1.0..1.0)));
+                let length_squared = v.length_squared();
+                if length_squared > 1e-6 && length_squared <= 1.0 {
+                    break v / length_squared.sqrt();
+                }
+            })
+            .collect();
+
+        let perm = [0; 3].map(|_| {
+            let mut p = (0..POINT_COUNT).collect::<Vec<_>>();
+            p.shuffle(&mut rng);
+            p
+        });
+
+        Self { gradients, perm }
+    }
+
+    // smooth noise in [-1, 1]
+    pub fn noise(&self, point: Vec3) -> f64 {
+        let floor = point.transform(|v| v.floor());
+        let frac = point - floor;
+        let cell = floor.transform(|v| v as i64);
+
+        let mut accumulated = 0.0;
+        for corner in 0..8 {
+            let offset = [0, 1, 2].map(|axis| (corner >> axis) & 1);
+            let hash = (0..3).fold(0, |acc, axis| {
+                let index = (cell[axis] + offset[axis] as i64) as usize & (POINT_COUNT - 1);
+                acc ^ self.perm[axis][index]
+            });
+
+            // trilinear weights with hermite smoothing on the distance from the corner
+            let weight = Vec3::new([0, 1, 2].map(|axis| frac[axis] - offset[axis] as f64));
+            let falloff = (0..3).fold(1.0, |acc, axis| {
+                let t = frac[axis];
+                let smooth = t * t * (3.0 - 2.0 * t);
+                acc * match offset[axis] {
+                    1 => smooth,
+                    _ => 1.0 - smooth,
+                }
+            });
+
+            accumulated += falloff * self.gradients[hash].dot(weight);
+        }
+
+        accumulated
+    }
+
+    // sum of `octaves` layers of noise, each twice the frequency and half the amplitude of the
+    // previous one
+    pub fn turbulence(&self, point: Vec3, octaves: u32) -> f64 {
+        let mut accumulated = 0.0;
+        let mut point = point;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            accumulated += weight * self.noise(point);
+            weight *= 0.5;
+            point = point * 2.0;
+        }
+
+        accumulated.abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded() {
+        let point = Vec3::new([0.3, 1.7, -4.2]);
+
+        assert_eq!(Perlin::new(42).noise(point), Perlin::new(42).noise(point));
+        assert_ne!(Perlin::new(42).noise(point), Perlin::new(7).noise(point));
+    }
+
+    #[test]
+    fn test_range() {
+        let perlin = Perlin::new(0);
+
+        // zero at the lattice points
+        assert_eq!(perlin.noise(Vec3::new([3.0, -2.0, 5.0])), 0.0);
+
+        for i in 0..1000 {
+            let t = i as f64 * 0.137;
+            let point = Vec3::new([t, t * 0.5 - 3.0, 10.0 - t * 1.3]);
+            let value = perlin.noise(point);
+            assert!((-1.0..=1.0).contains(&value));
+            assert!(perlin.turbulence(point, 7) >= 0.0);
+        }
+    }
+}
@@ -0,0 +1,603 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::bvh::BvhNode;
+use crate::color::Color;
+use crate::hittable::Hittable;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::{MeshBuffers, TriangleMesh};
+use crate::texture::ImageTexture;
+use crate::vec::Vector;
+
+type Vec3 = Vector<f64, 3>;
+type Vec2 = Vector<f64, 2>;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            LoadError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            LoadError::Parse { .. } => None,
+        }
+    }
+}
+
+// Material description as read from a .mtl file
+#[derive(Clone, Debug)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: Color,
+    pub specular: Color,
+    pub emission: Color,
+    pub shininess: f64,
+    pub refractive_index: f64,
+    pub dissolve: f64,
+    pub illum: Option<u32>,
+    pub diffuse_map: Option<PathBuf>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            diffuse: Color::new_one(0.8),
+            specular: Color::new_one(0.0),
+            emission: Color::new_one(0.0),
+            shininess: 0.0,
+            refractive_index: 1.0,
+            dissolve: 1.0,
+            illum: None,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // The mapping is a best effort: emissive materials become lights, transparent materials become
+    // glass, materials that are more specular than diffuse become metal and everything else is
+    // lambertian.
+    pub fn create_material(&self) -> Box<dyn Material> {
+        let max_component = |c: &Color| c.r().max(*c.g()).max(*c.b());
+
+        let is_transparent = self.dissolve < 1.0 || matches!(self.illum, Some(4 | 6 | 7 | 9));
+        let is_metallic = matches!(self.illum, Some(3))
+            || max_component(&self.specular) > max_component(&self.diffuse);
+
+        if !is_black(&self.emission) {
+            Box::new(DiffuseLight::new(self.emission.clone()))
+        } else if is_transparent {
+            Box::new(Dielectric::new(self.refractive_index))
+        } else if is_metallic {
+            let fuzz = (1.0 - self.shininess / 1000.0).clamp(0.0, 1.0);
+            Box::new(Metal::new(self.specular.clone(), fuzz))
+        } else {
+            match &self.diffuse_map {
+                Some(path) => {
+                    Box::new(Lambertian::with_texture(Box::new(ImageTexture::load(path))))
+                }
+                None => Box::new(Lambertian::new(self.diffuse.clone())),
+            }
+        }
+    }
+}
+
+fn is_black(color: &Color) -> bool {
+    *color.r() <= 0.0 && *color.g() <= 0.0 && *color.b() <= 0.0
+}
+
+// A group of faces from the same object/group that share a material
+pub struct ObjMesh {
+    pub name: String,
+    pub material: Option<String>,
+    pub mesh: TriangleMesh,
+}
+
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+}
+
+impl ObjModel {
+    pub fn triangle_count(&self) -> usize {
+        self.meshes.iter().map(|m| m.mesh.triangle_count()).sum()
+    }
+
+    pub fn into_bvh(self) -> BvhNode {
+        let objects = self
+            .meshes
+            .into_iter()
+            .map(|m| Box::new(m.mesh) as Box<dyn Hittable>)
+            .collect();
+        BvhNode::new(objects)
+    }
+}
+
+pub fn load_obj(path: &Path) -> Result<ObjModel, LoadError> {
+    let reader = open(path)?;
+    parse_obj(reader, path, load_mtl)
+}
+
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, MtlMaterial>, LoadError> {
+    let reader = open(path)?;
+    parse_mtl(reader, path)
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, LoadError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|source| LoadError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+// Position, uv and normal index of a face vertex (all zero based)
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct MeshBuilder {
+    name: String,
+    material: Option<String>,
+    vertex_map: HashMap<FaceVertex, usize>,
+    vertices: Vec<FaceVertex>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl MeshBuilder {
+    fn add_vertex(&mut self, vertex: FaceVertex) -> usize {
+        *self.vertex_map.entry(vertex).or_insert_with(|| {
+            self.vertices.push(vertex);
+            self.vertices.len() - 1
+        })
+    }
+
+    // normals and uvs are only kept if every vertex of the mesh has them
+    fn build(
+        self,
+        positions: &[Vec3],
+        uvs: &[Vec2],
+        normals: &[Vec3],
+        materials: &HashMap<String, MtlMaterial>,
+    ) -> ObjMesh {
+        let has_uvs = self.vertices.iter().all(|v| v.1.is_some());
+        let has_normals = self.vertices.iter().all(|v| v.2.is_some());
+
+        let mtl = match &self.material {
+            Some(name) => materials.get(name).cloned().unwrap_or_else(|| {
+                eprintln!("Material '{}' not found, using default material", name);
+                MtlMaterial::default()
+            }),
+            None => MtlMaterial::default(),
+        };
+
+        let buffers = MeshBuffers {
+            positions: self.vertices.iter().map(|v| positions[v.0]).collect(),
+            uvs: has_uvs.then(|| self.vertices.iter().map(|v| uvs[v.1.unwrap()]).collect()),
+            normals: has_normals.then(|| {
+                self.vertices
+                    .iter()
+                    .map(|v| normals[v.2.unwrap()])
+                    .collect()
+            }),
+            indices: self.indices,
+            material: Some(mtl.create_material()),
+        };
+
+        ObjMesh {
+            name: self.name,
+            material: self.material,
+            mesh: TriangleMesh::new(buffers),
+        }
+    }
+}
+
+fn parse_obj<R, F>(reader: R, path: &Path, mut load_mtl: F) -> Result<ObjModel, LoadError>
+where
+    R: BufRead,
+    F: FnMut(&Path) -> Result<HashMap<String, MtlMaterial>, LoadError>,
+{
+    let mut positions = Vec::<Vec3>::new();
+    let mut uvs = Vec::<Vec2>::new();
+    let mut normals = Vec::<Vec3>::new();
+    let mut materials = HashMap::<String, MtlMaterial>::new();
+
+    let mut finished = Vec::<MeshBuilder>::new();
+    let mut current = MeshBuilder::default();
+    let mut object_name = String::new();
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line_number = line_idx + 1;
+        let error = |message: String| LoadError::Parse {
+            path: path.to_path_buf(),
+            line: line_number,
+            message,
+        };
+
+        let line = line.map_err(|source| LoadError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            // w and the vertex colors some exporters add are not used
+            Some("v") => positions.push(parse_floats(tokens.take(3)).map_err(error)?.into()),
+            Some("vn") => normals.push(parse_floats(tokens).map_err(error)?.into()),
+            Some("vt") => {
+                // v is optional and w is not used
+                let mut tokens = tokens.chain(["0"]).take(2);
+                uvs.push(parse_floats(&mut tokens).map_err(error)?.into());
+            }
+            Some("f") => {
+                let counts = [positions.len(), uvs.len(), normals.len()];
+                let face = tokens
+                    .map(|t| parse_face_vertex(t, counts))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+
+                if face.len() < 3 {
+                    return Err(error(format!(
+                        "Face needs at least 3 vertices, got {}",
+                        face.len()
+                    )));
+                }
+
+                // fan triangulation (assumes convex polygons)
+                let indices = face
+                    .into_iter()
+                    .map(|v| current.add_vertex(v))
+                    .collect::<Vec<_>>();
+                for i in 1..indices.len() - 1 {
+                    current
+                        .indices
+                        .push([indices[0], indices[i], indices[i + 1]]);
+                }
+            }
+            Some("o") => {
+                object_name = tokens.collect::<Vec<_>>().join(" ");
+                let material = current.material.clone();
+                switch_mesh(&mut current, &mut finished, object_name.clone(), material);
+            }
+            Some("g") => {
+                let group = tokens.collect::<Vec<_>>().join(" ");
+                let name = match (object_name.is_empty(), group.is_empty()) {
+                    (_, true) => object_name.clone(),
+                    (true, false) => group,
+                    (false, false) => format!("{}/{}", object_name, group),
+                };
+                let material = current.material.clone();
+                switch_mesh(&mut current, &mut finished, name, material);
+            }
+            Some("usemtl") => {
+                let material = tokens.collect::<Vec<_>>().join(" ");
+                let name = current.name.clone();
+                switch_mesh(&mut current, &mut finished, name, Some(material));
+            }
+            Some("mtllib") => {
+                // a missing library only loses its materials, the meshes use the default one. An
+                // invalid library is still an error.
+                for file in tokens {
+                    match load_mtl(&base_dir.join(file)) {
+                        Ok(library) => materials.extend(library),
+                        Err(e @ LoadError::Parse { .. }) => return Err(e),
+                        Err(e @ LoadError::Io { .. }) => eprintln!(
+                            "{}:{}: {}, using default material",
+                            path.display(),
+                            line_number,
+                            e
+                        ),
+                    }
+                }
+            }
+            // comments and unsupported statements (s, l, p, ...)
+            _ => (),
+        }
+    }
+
+    if !current.indices.is_empty() {
+        finished.push(current);
+    }
+
+    let meshes = finished
+        .into_iter()
+        .map(|b| b.build(&positions, &uvs, &normals, &materials))
+        .collect();
+
+    Ok(ObjModel { meshes })
+}
+
+// starts a new mesh, the previous one is kept only if it has any face
+fn switch_mesh(
+    current: &mut MeshBuilder,
+    finished: &mut Vec<MeshBuilder>,
+    name: String,
+    material: Option<String>,
+) {
+    let previous = std::mem::replace(
+        current,
+        MeshBuilder {
+            name,
+            material,
+            ..MeshBuilder::default()
+        },
+    );
+    if !previous.indices.is_empty() {
+        finished.push(previous);
+    }
+}
+
+fn parse_mtl<R: BufRead>(
+    reader: R,
+    path: &Path,
+) -> Result<HashMap<String, MtlMaterial>, LoadError> {
+    let mut materials = HashMap::<String, MtlMaterial>::new();
+    let mut current: Option<MtlMaterial> = None;
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line_number = line_idx + 1;
+        let error = |message: String| LoadError::Parse {
+            path: path.to_path_buf(),
+            line: line_number,
+            message,
+        };
+
+        let line = line.map_err(|source| LoadError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut tokens = line.split_whitespace();
+
+        let keyword = match tokens.next() {
+            Some(k) if !k.starts_with('#') => k,
+            _ => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            if let Some(mtl) = current.replace(MtlMaterial {
+                name,
+                ..MtlMaterial::default()
+            }) {
+                materials.insert(mtl.name.clone(), mtl);
+            }
+            continue;
+        }
+
+        let mtl = match current.as_mut() {
+            Some(mtl) => mtl,
+            None => return Err(error(format!("'{}' before any 'newmtl'", keyword))),
+        };
+
+        let color = |tokens| -> Result<Color, LoadError> {
+            let [r, g, b] = parse_floats(tokens).map_err(error)?;
+            Ok(Color::new([r, g, b]))
+        };
+        let float = |tokens| -> Result<f64, LoadError> {
+            let [value] = parse_floats(tokens).map_err(error)?;
+            Ok(value)
+        };
+
+        match keyword {
+            "Kd" => mtl.diffuse = color(tokens)?,
+            "Ks" => mtl.specular = color(tokens)?,
+            "Ke" => mtl.emission = color(tokens)?,
+            "Ns" => mtl.shininess = float(tokens)?,
+            "Ni" => mtl.refractive_index = float(tokens)?,
+            "d" => mtl.dissolve = float(tokens)?,
+            "Tr" => mtl.dissolve = 1.0 - float(tokens)?,
+            "illum" => {
+                let value = tokens.next().unwrap_or_default();
+                let illum = value
+                    .parse::<u32>()
+                    .map_err(|_| error(format!("Invalid illumination model '{}'", value)))?;
+                mtl.illum = Some(illum);
+            }
+            // texture options come before the file name
+            "map_Kd" => match tokens.last() {
+                Some(file) => mtl.diffuse_map = Some(base_dir.join(file)),
+                None => return Err(error("Missing texture file name".to_string())),
+            },
+            _ => (),
+        }
+    }
+
+    if let Some(mtl) = current {
+        materials.insert(mtl.name.clone(), mtl);
+    }
+
+    Ok(materials)
+}
+
+fn parse_floats<'a, I, const N: usize>(tokens: I) -> Result<[f64; N], String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let values = tokens
+        .map(|t| {
+            t.parse::<f64>()
+                .map_err(|_| format!("Invalid number '{}'", t))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    values
+        .try_into()
+        .map_err(|v: Vec<f64>| format!("Expected {} values, got {}", N, v.len()))
+}
+
+// parses "v", "v/vt", "v//vn" or "v/vt/vn", indices can be negative (relative to the end)
+fn parse_face_vertex(token: &str, counts: [usize; 3]) -> Result<FaceVertex, String> {
+    let mut parts = token.split('/');
+    let mut index = |kind: usize| -> Result<Option<usize>, String> {
+        let part = match parts.next() {
+            Some(p) if !p.is_empty() => p,
+            _ => return Ok(None),
+        };
+
+        let count = counts[kind];
+        let value = part
+            .parse::<i64>()
+            .map_err(|_| format!("Invalid face index '{}'", part))?;
+        let resolved = match value {
+            v if v > 0 => v - 1,
+            v if v < 0 => count as i64 + v,
+            _ => return Err("Face index can't be 0".to_string()),
+        };
+
+        match resolved {
+            i if i >= 0 && (i as usize) < count => Ok(Some(i as usize)),
+            _ => Err(format!("Face index {} out of range", value)),
+        }
+    };
+
+    let position = index(0)?.ok_or_else(|| format!("Missing vertex index in '{}'", token))?;
+    let uv = index(1)?;
+    let normal = index(2)?;
+
+    Ok((position, uv, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<ObjModel, LoadError> {
+        parse_obj(source.as_bytes(), Path::new("test.obj"), |_| {
+            Ok(HashMap::new())
+        })
+    }
+
+    #[test]
+    fn test_triangulation() {
+        let model = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\n\
+             f 1 2 3 4\n\
+             o second\n\
+             f -5 -4 -1\n",
+        )
+        .unwrap();
+
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.meshes[0].mesh.triangle_count(), 2);
+        assert_eq!(model.meshes[1].name, "second");
+        assert_eq!(model.meshes[1].mesh.buffers().indices, vec![[0, 1, 2]]);
+        assert_eq!(model.triangle_count(), 3);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let model = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n\
+             f 1/1/1 2/2/1 3/3/1\n",
+        )
+        .unwrap();
+
+        let buffers = model.meshes[0].mesh.buffers();
+        assert_eq!(buffers.positions.len(), 3);
+        assert_eq!(buffers.uvs.as_ref().map(|u| u.len()), Some(3));
+        assert_eq!(buffers.normals.as_ref().map(|n| n.len()), Some(3));
+    }
+
+    #[test]
+    fn test_extra_components() {
+        let model = parse(
+            "v 0 0 0 1\n\
+             v 1 0 0 0.5 0.5 0.5\n\
+             v 0 1 0 1 0 0\n\
+             f 1 2 3\n",
+        )
+        .unwrap();
+
+        let buffers = model.meshes[0].mesh.buffers();
+        assert_eq!(buffers.positions[1], Vec3::new([1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:4: Face index 4 out of range");
+
+        let err = parse("v 0 0\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:1: Expected 3 values, got 2");
+
+        let err = parse("v 0 0 0\nv 1 x 0\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:2: Invalid number 'x'");
+    }
+
+    #[test]
+    fn test_missing_mtl() {
+        let source = "mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n";
+        let model = parse_obj(source.as_bytes(), Path::new("test.obj"), |path| {
+            Err(LoadError::Io {
+                path: path.to_path_buf(),
+                source: io::Error::from(io::ErrorKind::NotFound),
+            })
+        })
+        .unwrap();
+
+        assert_eq!(model.triangle_count(), 1);
+
+        let err = parse_obj(source.as_bytes(), Path::new("test.obj"), |path| {
+            Err(LoadError::Parse {
+                path: path.to_path_buf(),
+                line: 2,
+                message: "Invalid number 'x'".to_string(),
+            })
+        })
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "missing.mtl:2: Invalid number 'x'");
+    }
+
+    #[test]
+    fn test_mtl() {
+        let source = "# comment\n\
+                      newmtl red\nKd 1 0 0\n\
+                      newmtl glass\nNi 1.5\nd 0.2\nmap_Kd -s 1 1 1 tex/glass.png\n";
+        let materials = parse_mtl(source.as_bytes(), Path::new("dir/test.mtl")).unwrap();
+
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials["red"].diffuse, Color::new([1.0, 0.0, 0.0]));
+        assert_eq!(materials["glass"].refractive_index, 1.5);
+        assert_eq!(materials["glass"].dissolve, 0.2);
+        assert_eq!(
+            materials["glass"].diffuse_map,
+            Some(PathBuf::from("dir/tex/glass.png"))
+        );
+
+        let err = parse_mtl("Kd 1 1 1\n".as_bytes(), Path::new("test.mtl"))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "test.mtl:1: 'Kd' before any 'newmtl'");
+    }
+}
@@ -0,0 +1,604 @@
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::{ImageBuffer, ImageResult, Rgb};
+
+use crate::color::Color;
+use crate::ray_tracer::{Dimension, Image};
+use crate::tonemap::ToneMapping;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    // binary P6
+    Ppm,
+    // plain text P3
+    PpmAscii,
+    Png,
+    Png16,
+    // radiance rgbe
+    Hdr,
+    // portable float map
+    Pfm,
+    // uncompressed openexr
+    Exr,
+    // zip compressed openexr
+    ExrZip,
+}
+
+impl OutputFormat {
+    // `.png` is written as 8-bit png, `.ppm` as binary ppm and `.exr` zip compressed
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(OutputFormat::Ppm),
+            "png" => Some(OutputFormat::Png),
+            "hdr" => Some(OutputFormat::Hdr),
+            "pfm" => Some(OutputFormat::Pfm),
+            "exr" => Some(OutputFormat::ExrZip),
+            _ => None,
+        }
+    }
+
+    // largest value of a color channel, None for the formats that store the radiance as is
+    pub fn max_value(&self) -> Option<u16> {
+        match self {
+            OutputFormat::Ppm | OutputFormat::PpmAscii | OutputFormat::Png => Some(u8::MAX as u16),
+            OutputFormat::Png16 => Some(u16::MAX),
+            OutputFormat::Hdr | OutputFormat::Pfm | OutputFormat::Exr | OutputFormat::ExrZip => {
+                None
+            }
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ppm" => Ok(OutputFormat::Ppm),
+            "ppm-ascii" => Ok(OutputFormat::PpmAscii),
+            "png" => Ok(OutputFormat::Png),
+            "png16" => Ok(OutputFormat::Png16),
+            "hdr" => Ok(OutputFormat::Hdr),
+            "pfm" => Ok(OutputFormat::Pfm),
+            "exr" => Ok(OutputFormat::Exr),
+            "exr-zip" => Ok(OutputFormat::ExrZip),
+            _ => Err(format!("Unknown output format '{}'", s)),
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Ppm => write!(f, "ppm"),
+            OutputFormat::PpmAscii => write!(f, "ppm-ascii"),
+            OutputFormat::Png => write!(f, "png"),
+            OutputFormat::Png16 => write!(f, "png16"),
+            OutputFormat::Hdr => write!(f, "hdr"),
+            OutputFormat::Pfm => write!(f, "pfm"),
+            OutputFormat::Exr => write!(f, "exr"),
+            OutputFormat::ExrZip => write!(f, "exr-zip"),
+        }
+    }
+}
+
+// tone maps a linear color and maps it to integers in [0, max_value], every low dynamic range
+// format goes through here
+pub fn quantize(color: &Color, tone_mapping: &ToneMapping, max_value: u16) -> [u16; 3] {
+    let scale = max_value as f64 + 1.0;
+    let color = tone_mapping.apply(color);
+    [*color.r(), *color.g(), *color.b()].map(|v| (v * scale).min(max_value as f64) as u16)
+}
+
+// path of an extra output written next to `output`, e.g. ("image.png", "albedo") -> "image.albedo.png"
+pub fn sibling_path(output: &Path, name: &str) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match output.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, name, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, name),
+    };
+    output.with_file_name(file_name)
+}
+
+// the high dynamic range formats store the radiance as is, without tone mapping
+pub fn write_image(
+    image: &Image,
+    path: &Path,
+    format: OutputFormat,
+    tone_mapping: &ToneMapping,
+) -> ImageResult<()> {
+    match format.max_value() {
+        Some(max_value) => write_ldr(image, path, format, max_value, tone_mapping),
+        None => {
+            let mut file = BufWriter::new(File::create(path)?);
+            match format {
+                OutputFormat::Hdr => write_rgbe(image, &mut file)?,
+                OutputFormat::Pfm => write_pfm(image, &mut file)?,
+                OutputFormat::Exr => write_exr(image, &mut file, ExrCompression::None)?,
+                _ => write_exr(image, &mut file, ExrCompression::Zip)?,
+            }
+            Ok(file.flush()?)
+        }
+    }
+}
+
+fn write_ldr(
+    image: &Image,
+    path: &Path,
+    format: OutputFormat,
+    max_value: u16,
+    tone_mapping: &ToneMapping,
+) -> ImageResult<()> {
+    let Dimension { width, height } = image.dimension;
+    let samples = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| quantize(pixel, tone_mapping, max_value));
+
+    match format {
+        OutputFormat::PpmAscii => {
+            let mut file = BufWriter::new(File::create(path)?);
+            write!(file, "P3\n{} {}\n{}\n", width, height, max_value)?;
+            for pixel in image.pixels.iter() {
+                let [r, g, b] = quantize(pixel, tone_mapping, max_value);
+                writeln!(file, "{} {} {}", r, g, b)?;
+            }
+            file.flush()?;
+        }
+        OutputFormat::Png => {
+            let buffer = samples.map(|v| v as u8).collect::<Vec<_>>();
+            ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, buffer)
+                .expect("Pixel count must match the image dimension")
+                .save_with_format(path, image::ImageFormat::Png)?;
+        }
+        OutputFormat::Png16 => {
+            let buffer = samples.collect::<Vec<_>>();
+            ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, buffer)
+                .expect("Pixel count must match the image dimension")
+                .save_with_format(path, image::ImageFormat::Png)?;
+        }
+        _ => {
+            let mut file = BufWriter::new(File::create(path)?);
+            write!(file, "P6\n{} {}\n{}\n", width, height, max_value)?;
+            file.write_all(&samples.map(|v| v as u8).collect::<Vec<_>>())?;
+            file.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+// shared exponent encoding, see: "Real Pixels" (Ward, Graphics Gems II)
+fn to_rgbe(color: &Color) -> [u8; 4] {
+    let rgb = [*color.r(), *color.g(), *color.b()].map(|v| v.max(0.0));
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    if !max.is_finite() || max < 1e-32 {
+        return [0; 4];
+    }
+
+    // max = mantissa * 2^exponent with the mantissa in [0.5, 1)
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    let [r, g, b] = rgb.map(|v| (v * scale).min(255.0) as u8);
+    [r, g, b, (exponent + 128) as u8]
+}
+
+// uncompressed (flat) scanlines, every reader supports them
+fn write_rgbe(image: &Image, writer: &mut impl Write) -> std::io::Result<()> {
+    let Dimension { width, height } = image.dimension;
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    for pixel in image.pixels.iter() {
+        writer.write_all(&to_rgbe(pixel))?;
+    }
+    Ok(())
+}
+
+fn write_pfm(image: &Image, writer: &mut impl Write) -> std::io::Result<()> {
+    let Dimension { width, height } = image.dimension;
+    // the negative scale means little endian
+    write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+
+    // rows are stored from bottom to top
+    for row in image.pixels.chunks(width as usize).rev() {
+        for pixel in row {
+            for value in [*pixel.r(), *pixel.g(), *pixel.b()] {
+                writer.write_all(&(value as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ExrCompression {
+    None,
+    Zip,
+}
+
+impl ExrCompression {
+    // values of the "compression" attribute
+    fn id(&self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn lines_per_chunk(&self) -> usize {
+        match self {
+            ExrCompression::None => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// Single part scanline OpenEXR with 32-bit float R, G and B channels. See: "The OpenEXR File
+// Layout" (openexr.com).
+fn write_exr(
+    image: &Image,
+    writer: &mut impl Write,
+    compression: ExrCompression,
+) -> std::io::Result<()> {
+    let Dimension { width, height } = image.dimension;
+    let (width, height) = (width as usize, height as usize);
+
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+
+    // channels must be sorted by name, the pixel data uses the same order
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        channels.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    channels.push(0);
+
+    let window = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<_>>();
+
+    exr_attribute(&mut header, "channels", "chlist", &channels);
+    exr_attribute(
+        &mut header,
+        "compression",
+        "compression",
+        &[compression.id()],
+    );
+    exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]); // increasing y
+    exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    let lines_per_chunk = compression.lines_per_chunk();
+    let chunks = (0..height)
+        .step_by(lines_per_chunk)
+        .map(|first_line| {
+            let lines = first_line..(first_line + lines_per_chunk).min(height);
+            let mut data = Vec::with_capacity(lines.len() * width * 3 * 4);
+            for line in lines {
+                let row = &image.pixels[line * width..(line + 1) * width];
+                for channel in [Color::b, Color::g, Color::r] {
+                    for pixel in row {
+                        data.extend_from_slice(&(*channel(pixel) as f32).to_le_bytes());
+                    }
+                }
+            }
+
+            let data = match compression {
+                ExrCompression::None => data,
+                ExrCompression::Zip => exr_zip(data),
+            };
+            (first_line, data)
+        })
+        .collect::<Vec<_>>();
+
+    // the offset table points to the start of every chunk in the file
+    let mut offset = (header.len() + chunks.len() * 8) as u64;
+    for (_, data) in chunks.iter() {
+        header.extend_from_slice(&offset.to_le_bytes());
+        offset += 8 + data.len() as u64;
+    }
+    writer.write_all(&header)?;
+
+    for (first_line, data) in chunks {
+        writer.write_all(&(first_line as i32).to_le_bytes())?;
+        writer.write_all(&(data.len() as i32).to_le_bytes())?;
+        writer.write_all(&data)?;
+    }
+    Ok(())
+}
+
+// bytes are split into two halves (even and odd positions) and delta encoded before deflating,
+// the data is stored as is if compressing doesn't make it smaller
+fn exr_zip(data: Vec<u8>) -> Vec<u8> {
+    if data.is_empty() {
+        return data;
+    }
+
+    let half = data.len().div_ceil(2);
+    let mut reordered = vec![0; data.len()];
+    for (i, byte) in data.iter().enumerate() {
+        reordered[i / 2 + (i % 2) * half] = *byte;
+    }
+
+    let mut previous = reordered[0];
+    for byte in reordered.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&reordered)
+        .expect("Writing to memory can't fail");
+    let compressed = encoder.finish().expect("Writing to memory can't fail");
+
+    match compressed.len() < data.len() {
+        true => compressed,
+        false => data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::{Operator, Transfer};
+
+    fn test_image() -> Image {
+        Image {
+            pixels: vec![
+                Color::new([0.0, 0.25, 1.0]),
+                Color::new([2.0, -1.0, 0.5]),
+                Color::new([1.0, 1.0, 1.0]),
+                Color::new([0.0, 0.0, 0.0]),
+            ],
+            dimension: Dimension {
+                width: 2,
+                height: 2,
+            },
+            aovs: None,
+            sample_counts: None,
+        }
+    }
+
+    #[test]
+    fn test_quantize() {
+        let gamma2 = ToneMapping {
+            transfer: Transfer::Gamma2,
+            ..Default::default()
+        };
+        assert_eq!(
+            quantize(&Color::new([0.0, 0.25, 1.0]), &gamma2, 255),
+            [0, 128, 255]
+        );
+        assert_eq!(
+            quantize(&Color::new([2.0, -1.0, 0.5]), &gamma2, 255),
+            [255, 0, 181]
+        );
+
+        let srgb = ToneMapping::default();
+        assert_eq!(
+            quantize(&Color::new([0.0, 0.5, 1.0]), &srgb, 255),
+            [0, 188, 255]
+        );
+        assert_eq!(
+            quantize(&Color::new_one(1.0), &srgb, u16::MAX),
+            [u16::MAX; 3]
+        );
+    }
+
+    #[test]
+    fn test_write_read() {
+        let dir = std::env::temp_dir().join(format!("rtr-output-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = test_image();
+        let tone_mapping = ToneMapping {
+            exposure: 0.5,
+            operator: Operator::Aces,
+            ..Default::default()
+        };
+
+        for format in [OutputFormat::Ppm, OutputFormat::PpmAscii, OutputFormat::Png] {
+            let path = dir.join(format!("image-{}.img", format));
+            write_image(&image, &path, format, &tone_mapping).unwrap();
+
+            let read = image::io::Reader::open(&path)
+                .unwrap()
+                .with_guessed_format()
+                .unwrap()
+                .decode()
+                .unwrap()
+                .into_rgb8();
+            let expected = image
+                .pixels
+                .iter()
+                .flat_map(|p| quantize(p, &tone_mapping, 255));
+            assert!(read.into_raw().into_iter().map(u16::from).eq(expected));
+        }
+
+        let path = dir.join("image.png");
+        write_image(&image, &path, OutputFormat::Png16, &tone_mapping).unwrap();
+        let read = image::open(&path).unwrap().into_rgb16();
+        let expected = image
+            .pixels
+            .iter()
+            .flat_map(|p| quantize(p, &tone_mapping, u16::MAX));
+        assert!(read.into_raw().into_iter().eq(expected));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format() {
+        let format = |p: &str| OutputFormat::from_extension(Path::new(p));
+        assert_eq!(format("out/image.PNG"), Some(OutputFormat::Png));
+        assert_eq!(format("image.ppm"), Some(OutputFormat::Ppm));
+        assert_eq!(format("image.jpg"), None);
+        assert_eq!(format("image"), None);
+        assert_eq!(format("render.exr"), Some(OutputFormat::ExrZip));
+        assert_eq!("png16".parse(), Ok(OutputFormat::Png16));
+    }
+
+    #[test]
+    fn test_rgbe() {
+        assert_eq!(to_rgbe(&Color::new([1.0, 0.5, 0.25])), [128, 64, 32, 129]);
+        assert_eq!(to_rgbe(&Color::new([40.0, 0.0, -1.0])), [160, 0, 0, 134]);
+        assert_eq!(to_rgbe(&Color::new_one(0.0)), [0; 4]);
+
+        let decode = |rgbe: [u8; 4]| (rgbe[0] as f64 + 0.5) * 2f64.powi(rgbe[3] as i32 - 136);
+        for value in [0.001, 0.3, 1.0, 7.5, 1234.5] {
+            let decoded = decode(to_rgbe(&Color::new([value, 0.0, 0.0])));
+            assert!((decoded - value).abs() / value < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_pfm() {
+        let mut bytes = Vec::new();
+        write_pfm(&test_image(), &mut bytes).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let floats = bytes[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        // bottom row first, values are not clamped
+        assert_eq!(floats.len(), 12);
+        assert_eq!(floats[..3], [1.0, 1.0, 1.0]);
+        assert_eq!(floats[6..9], [0.0, 0.25, 1.0]);
+        assert_eq!(floats[9..], [2.0, -1.0, 0.5]);
+    }
+
+    // returns the (first line, data) of every chunk, decompressed
+    fn read_exr_chunks(bytes: &[u8], compressed: bool) -> Vec<(i32, Vec<u8>)> {
+        assert_eq!(bytes[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        // skip the attributes: name, type, size and value
+        let mut position = 8;
+        while bytes[position] != 0 {
+            for _ in 0..2 {
+                position += bytes[position..].iter().position(|b| *b == 0).unwrap() + 1;
+            }
+            let size = i32::from_le_bytes(bytes[position..position + 4].try_into().unwrap());
+            position += 4 + size as usize;
+        }
+        let table = position + 1;
+
+        let read_i32 = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let first_offset = u64::from_le_bytes(bytes[table..table + 8].try_into().unwrap());
+        let chunk_count = (first_offset as usize - table) / 8;
+
+        (0..chunk_count)
+            .map(|i| {
+                let at = table + i * 8;
+                let offset = u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap()) as usize;
+                let size = read_i32(offset + 4) as usize;
+                let data = bytes[offset + 8..offset + 8 + size].to_vec();
+                if !compressed {
+                    return (read_i32(offset), data);
+                }
+
+                let mut decoder = flate2::read::ZlibDecoder::new(&data[..]);
+                let mut reordered = Vec::new();
+                std::io::Read::read_to_end(&mut decoder, &mut reordered).unwrap();
+                for i in 1..reordered.len() {
+                    reordered[i] = reordered[i]
+                        .wrapping_add(reordered[i - 1])
+                        .wrapping_sub(128);
+                }
+                let half = reordered.len().div_ceil(2);
+                let data = (0..reordered.len())
+                    .map(|i| reordered[i / 2 + (i % 2) * half])
+                    .collect();
+                (read_i32(offset), data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_exr() {
+        let dimension = Dimension {
+            width: 20,
+            height: 35,
+        };
+        let pixels = (0..700)
+            .map(|i| Color::new([i as f64 * 0.1, 0.5, (i % 20) as f64]))
+            .collect();
+        let image = Image {
+            pixels,
+            dimension,
+            aovs: None,
+            sample_counts: None,
+        };
+
+        let mut uncompressed = Vec::new();
+        write_exr(&image, &mut uncompressed, ExrCompression::None).unwrap();
+        let lines = read_exr_chunks(&uncompressed, false);
+        assert_eq!(lines.len(), 35);
+
+        // channels are stored in the order B, G, R
+        let (y, data) = &lines[1];
+        let floats = data
+            .chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(*y, 1);
+        assert_eq!(floats[..3], [0.0, 1.0, 2.0]);
+        assert_eq!(floats[20], 0.5);
+        assert_eq!(floats[40], 2.0);
+
+        let mut zip = Vec::new();
+        write_exr(&image, &mut zip, ExrCompression::Zip).unwrap();
+        assert!(zip.len() < uncompressed.len());
+        let chunks = read_exr_chunks(&zip, true);
+        assert_eq!(chunks.len(), 3);
+        for (i, (y, data)) in chunks.into_iter().enumerate() {
+            assert_eq!(y, i as i32 * 16);
+            let expected = lines[i * 16..(i * 16 + 16).min(35)]
+                .iter()
+                .flat_map(|(_, line)| line.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(data, expected);
+        }
+    }
+}
@@ -83,15 +83,26 @@ impl Div<usize> for UpdateRecord {
 
 pub struct ProgressTracker {
     min: isize,
-    max: isize,
+    // none if the work only stops with the time limit
+    max: Option<isize>,
     current: isize,
     first_update: Instant,
     last_update: Instant,
     records: MovingAverage<UpdateRecord, 32>,
+    time_limit: Option<Duration>,
 }
 
 impl ProgressTracker {
     pub fn new(min: isize, max: isize) -> Self {
+        Self::with_max(min, Some(max))
+    }
+
+    // the progress and eta only come from the time limit (see `with_time_limit`)
+    pub fn unbounded(min: isize) -> Self {
+        Self::with_max(min, None)
+    }
+
+    fn with_max(min: isize, max: Option<isize>) -> Self {
         Self {
             min,
             max,
@@ -99,9 +110,17 @@ impl ProgressTracker {
             first_update: Instant::now(),
             last_update: Instant::now(),
             records: MovingAverage::new(),
+            time_limit: None,
         }
     }
 
+    // the work stops when the time is up, even if max isn't reached, the progress and eta take the
+    // time limit into account
+    pub fn with_time_limit(mut self, time_limit: Option<Duration>) -> Self {
+        self.time_limit = time_limit;
+        self
+    }
+
     pub fn update(&mut self, new_current: isize) {
         let last = self.current;
         self.current = new_current;
@@ -118,25 +137,44 @@ impl ProgressTracker {
     }
 
     pub fn progress(&self) -> f64 {
-        (self.current - self.min) as f64 / (self.max - self.min) as f64 * 100.0
+        let progress = match self.max {
+            Some(max) => (self.current - self.min) as f64 / (max - self.min) as f64 * 100.0,
+            None => 0.0,
+        };
+        match self.time_limit {
+            Some(limit) => {
+                let time_progress = self.get_elapsed().as_secs_f64() / limit.as_secs_f64() * 100.0;
+                progress.max(time_progress.min(100.0))
+            }
+            None => progress,
+        }
     }
 
     pub fn get_eta(&self) -> Duration {
         let UpdateRecord { time, diff } = self.records.average();
         let speed = diff as f64 / time.as_secs_f64();
-        if speed == 0.0 {
-            Duration::from_secs(0)
-        } else {
-            let remaining = (self.max - self.current) as f64;
-            Duration::from_secs_f64(remaining / speed)
+        let eta = match self.max {
+            _ if speed == 0.0 => Duration::from_secs(0),
+            Some(max) => Duration::from_secs_f64((max - self.current) as f64 / speed),
+            None => Duration::MAX,
+        };
+
+        match self.time_limit {
+            Some(limit) => eta.min(limit.saturating_sub(self.get_elapsed())),
+            None => eta,
         }
     }
 
+    pub fn is_time_up(&self) -> bool {
+        self.time_limit
+            .is_some_and(|limit| self.get_elapsed() >= limit)
+    }
+
     pub fn get_elapsed(&self) -> Duration {
         self.first_update.elapsed()
     }
 
-    pub fn max(&self) -> isize {
+    pub fn max(&self) -> Option<isize> {
         self.max
     }
 }
@@ -1,10 +1,16 @@
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
 use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::aov::{self, AovBuffers, AovPixel};
 use crate::color::Color;
-use crate::hittable::{HitResult, Hittable};
+use crate::hittable::{HitRecord, HitResult, Hittable, HittableList};
 use crate::interval::Interval;
-use crate::material::ScatterResult;
+use crate::material::{Material, ScatterResult};
 use crate::progress_tracker::ProgressTracker;
 use crate::ray::Ray;
 use crate::vec::Vector;
@@ -13,16 +19,93 @@ use crate::{util, vec};
 type Vec3 = Vector<f64, 3>;
 type Ray3 = Ray<f64, 3>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Dimension {
     pub width: u32,
     pub height: u32,
 }
 
+// linear radiance, not clamped so nothing is lost for hdr outputs
 #[derive(Debug)]
 pub struct Image {
     pub pixels: Vec<Color>,
     pub dimension: Dimension,
+    // only rendered if `TracerParams::render_aovs` is set
+    pub aovs: Option<AovBuffers>,
+    // number of samples taken for each pixel
+    pub sample_counts: Option<Vec<u32>>,
+}
+
+impl Image {
+    // Heatmap of the samples taken per pixel. With `display` the counts are mapped from black (few)
+    // to white (the most samples of a pixel) for viewing in an ldr format, otherwise the raw
+    // counts are kept.
+    pub fn sample_heatmap(&self, display: bool) -> Option<Image> {
+        let counts = self.sample_counts.as_ref()?;
+        let max_samples = counts.iter().copied().max().unwrap_or(0);
+        let pixels = counts
+            .iter()
+            .map(|&count| match display {
+                true => heatmap_color(count as f64 / max_samples.max(1) as f64),
+                false => Color::new_one(count as f64),
+            })
+            .collect();
+
+        Some(Image {
+            pixels,
+            dimension: self.dimension.clone(),
+            aovs: None,
+            sample_counts: None,
+        })
+    }
+}
+
+// black -> blue -> red -> yellow -> white
+fn heatmap_color(value: f64) -> Color {
+    const STOPS: [[f64; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [1.0, 1.0, 1.0],
+    ];
+
+    let position = value.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let index = (position as usize).min(STOPS.len() - 2);
+    let t = position - index as f64;
+    Color::new(STOPS[index]) * (1.0 - t) + Color::new(STOPS[index + 1]) * t
+}
+
+// color of the rays that don't hit anything
+#[derive(Clone, Debug)]
+pub enum Background {
+    None,
+    Solid(Color),
+    Gradient { bottom: Color, top: Color },
+}
+
+impl Background {
+    // the white to blue gradient sky
+    pub fn sky() -> Self {
+        Background::Gradient {
+            bottom: Color::new_one(1.0),
+            top: Color::new([0.5, 0.7, 1.0]),
+        }
+    }
+
+    pub fn value(&self, ray: &Ray3) -> Color {
+        match self {
+            Background::None => Color::new_one(0.0),
+            Background::Solid(color) => color.clone(),
+            Background::Gradient { bottom, top } => {
+                let direction = ray.direction.unit_vector();
+
+                // lerp
+                let a = 0.5 * (direction.data[1] + 1.0);
+                bottom.clone() * (1.0 - a) + top.clone() * a
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -36,6 +119,35 @@ pub struct TracerParams {
     pub focus_distance: f64,
     pub look_from: Vec3,
     pub look_at: Vec3,
+    pub background: Background,
+    pub tile_size: u32,
+    // base seed of the per-sample random streams, same seed gives the same image
+    pub seed: u64,
+    // also render the first-hit buffers (albedo, normal, depth, position and id)
+    pub render_aovs: bool,
+    // stop sampling pixels early once they converged, `sampling_rate` is the maximum then
+    pub adaptive: Option<AdaptiveSampling>,
+    // emissive objects sampled directly at every diffuse bounce, empty to only follow the
+    // scattered rays
+    pub lights: HittableList,
+}
+
+#[derive(Clone, Debug)]
+pub struct AdaptiveSampling {
+    // samples taken before checking if the pixel converged
+    pub min_samples: u32,
+    // a pixel converged when the standard error of its mean luminance, relative to the luminance,
+    // is below the threshold
+    pub threshold: f64,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            min_samples: 16,
+            threshold: 0.01,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -70,6 +182,12 @@ pub struct RayTracer {
     camera: Camera,
     sampling_rate: u32,
     max_depth: u32,
+    background: Background,
+    tile_size: u32,
+    seed: u64,
+    render_aovs: bool,
+    adaptive: Option<AdaptiveSampling>,
+    lights: HittableList,
 }
 
 impl RayTracer {
@@ -138,23 +256,32 @@ impl RayTracer {
             camera,
             sampling_rate: params.sampling_rate,
             max_depth: params.max_depth,
+            background: params.background,
+            tile_size: params.tile_size,
+            seed: params.seed,
+            render_aovs: params.render_aovs,
+            adaptive: params.adaptive,
+            lights: params.lights,
         }
     }
 
     pub fn render(&self, scene: &dyn Hittable) -> Image {
-        let mut pixels = Vec::<Color>::with_capacity(
-            self.dimension.width as usize * self.dimension.height as usize,
-        );
+        let pixel_count = self.dimension.width as usize * self.dimension.height as usize;
+        let mut pixels = Vec::<Color>::with_capacity(pixel_count);
+        let mut aov_pixels = Vec::<AovPixel>::with_capacity(pixel_count);
+        let mut sample_counts = Vec::<u32>::with_capacity(pixel_count);
 
         let Dimension { width, height } = self.dimension;
         let mut tracker = ProgressTrackerWrapper::new(width, height as usize);
 
         for row in 0..height {
             for col in 0..width {
-                let color = self
-                    .sample_color_at(col, row, scene)
-                    .clamp((0.0, 1.0).into());
+                let (color, sample_count) = self.sample_color_at(col, row, scene);
                 pixels.push(color);
+                sample_counts.push(sample_count);
+                if self.render_aovs {
+                    aov_pixels.push(self.sample_aovs_at(col, row, scene));
+                }
 
                 tracker.update(row as usize, (col + 1) as usize);
             }
@@ -163,132 +290,464 @@ impl RayTracer {
         Image {
             pixels,
             dimension: self.dimension.clone(),
+            aovs: self.aov_buffers(aov_pixels),
+            sample_counts: Some(sample_counts),
         }
     }
 
     pub fn render_multi(&self, scene: &(dyn Hittable + Sync)) -> Image {
-        let concurrency_level: usize = thread::available_parallelism()
-            .unwrap_or(NonZeroUsize::new(1).unwrap())
-            .get();
-        let chunk_size = self.dimension.height as usize / concurrency_level;
+        let Dimension { width, height } = self.dimension;
+        let mut pixels = vec![Color::new_one(0.0); width as usize * height as usize];
+        let mut sample_counts = vec![0; pixels.len()];
+        let mut aov_pixels = match self.render_aovs {
+            true => vec![AovPixel::default(); pixels.len()],
+            false => Vec::new(),
+        };
 
-        enum SampleResult {
-            Color(usize, Color),
-            None,
+        let tracker = Mutex::new(ProgressTrackerWrapper::new(width, height as usize));
+        let output = TileOutput::new(&mut pixels, width);
+        let aov_output = TileOutput::new(&mut aov_pixels, width);
+        let count_output = TileOutput::new(&mut sample_counts, width);
+
+        self.run_tiles(&tracker, 0, |tile| {
+            for (col, row) in tile.pixels() {
+                let (color, count) = self.sample_color_at(col, row, scene);
+
+                // SAFETY: each tile is taken from the queue exactly once and tiles don't overlap,
+                // so no other thread writes to this pixel
+                unsafe {
+                    output.write(col, row, color);
+                    count_output.write(col, row, count);
+                }
+
+                if self.render_aovs {
+                    let aov_pixel = self.sample_aovs_at(col, row, scene);
+                    // SAFETY: same as above
+                    unsafe { aov_output.write(col, row, aov_pixel) };
+                }
+            }
+        });
+
+        Image {
+            pixels,
+            dimension: self.dimension.clone(),
+            aovs: self.aov_buffers(aov_pixels),
+            sample_counts: Some(sample_counts),
         }
+    }
 
-        let (tx, rx) = std::sync::mpsc::channel::<SampleResult>();
+    // Renders in passes of `samples_per_pass` samples for every pixel, and calls `on_snapshot` with
+    // the current estimate at most every `snapshot_interval` and once more at the end (e.g. to save
+    // a checkpoint the render can be continued from with more samples). Stops when every pixel has
+    // `sampling_rate` samples or converged. With a time limit the sampling rate is ignored and no
+    // tile is started once the time is up, so the last pass may only cover part of the image.
+    //
+    // The samples are the same as the ones of `render` and `render_multi`, so without a time limit
+    // the result is the same too. Rendering continues from `resume` if given (e.g. a checkpoint of
+    // an earlier render with the same parameters), the result is the same as if it never stopped.
+    pub fn render_progressive(
+        &self,
+        scene: &(dyn Hittable + Sync),
+        params: &ProgressiveParams,
+        resume: Option<Accumulator>,
+        mut on_snapshot: impl FnMut(&Accumulator),
+    ) -> Image {
+        let Dimension { width, height } = self.dimension;
+        let pixel_count = width as usize * height as usize;
 
-        // interleaved rendering
-        thread::scope(|s| {
-            for i in 0..concurrency_level.into() {
-                let num_steps = match chunk_size * concurrency_level + i {
-                    x if x < self.dimension.height as usize => chunk_size + 1,
-                    _ => chunk_size,
-                };
-                let tx = tx.clone();
+        let mut accumulator = match resume {
+            Some(mut accumulator) => {
+                assert_eq!(accumulator.dimension, self.dimension);
+                accumulator.aovs = self.render_aov_buffers(scene);
+                accumulator
+            }
+            None => self.new_accumulator(scene),
+        };
+        let samples_per_pass = params.samples_per_pass.max(1);
+        // none when the render only stops with the time limit
+        let passes = match params.time_limit {
+            Some(_) => None,
+            None => Some(self.sampling_rate.div_ceil(samples_per_pass)),
+        };
+        let tracker = match passes {
+            Some(passes) => ProgressTrackerWrapper::new(width, height as usize * passes as usize),
+            None => ProgressTrackerWrapper::unbounded(width),
+        };
+        let tracker = Mutex::new(tracker.with_time_limit(params.time_limit));
 
-                s.spawn(move || {
-                    let mut tracker = match i {
-                        0 => Some(ProgressTrackerWrapper::new(self.dimension.width, num_steps)),
-                        _ => None,
-                    };
+        let mut last_snapshot = Instant::now();
+        // none if every pixel converged already (e.g. a finished adaptive render was resumed)
+        let sample_count = accumulator.min_sample_count();
+        let mut pass = sample_count.map_or(0, |sample_count| sample_count / samples_per_pass);
+        while sample_count.is_some() && passes.is_none_or(|passes| pass < passes) {
+            let until = (pass + 1).saturating_mul(samples_per_pass);
+            let until = match passes {
+                Some(_) => until.min(self.sampling_rate),
+                None => until,
+            };
+            self.render_pass(
+                scene,
+                &mut accumulator,
+                until,
+                &tracker,
+                pass as usize * pixel_count,
+            );
 
-                    for count in 0..num_steps {
-                        let row = (count as usize * concurrency_level + i) as u32;
-                        for col in 0..self.dimension.width {
-                            let index = (row * self.dimension.width + col) as usize;
-                            let color = self
-                                .sample_color_at(col, row, scene)
-                                .clamp(Interval::new(0.0, 1.0));
+            pass += 1;
+            // with a time limit there is no last pass, the render is also done once every pixel
+            // converged
+            if passes == Some(pass) || accumulator.min_sample_count().is_none() {
+                break;
+            }
+            if tracker.lock().unwrap().is_time_up() {
+                eprintln!("\nTime limit reached during pass {}", pass);
+                break;
+            }
+            if last_snapshot.elapsed() >= params.snapshot_interval {
+                on_snapshot(&accumulator);
+                last_snapshot = Instant::now();
+            }
+        }
 
-                            tx.send(SampleResult::Color(index, color)).unwrap();
+        on_snapshot(&accumulator);
+        accumulator.image()
+    }
 
-                            tracker
-                                .as_mut()
-                                .map(|v| v.update(count, (col + 1) as usize));
+    // empty estimate, the aovs don't get refined so they're rendered right away
+    fn new_accumulator(&self, scene: &(dyn Hittable + Sync)) -> Accumulator {
+        let Dimension { width, height } = self.dimension;
+        Accumulator {
+            pixels: vec![PixelState::default(); width as usize * height as usize],
+            aovs: self.render_aov_buffers(scene),
+            dimension: self.dimension.clone(),
+        }
+    }
+
+    fn render_aov_buffers(&self, scene: &(dyn Hittable + Sync)) -> Option<AovBuffers> {
+        if !self.render_aovs {
+            return None;
+        }
+
+        let Dimension { width, height } = self.dimension;
+        let mut aov_pixels = vec![AovPixel::default(); width as usize * height as usize];
+        let tracker = Mutex::new(ProgressTrackerWrapper::new(width, height as usize));
+        let aov_output = TileOutput::new(&mut aov_pixels, width);
+        self.run_tiles(&tracker, 0, |tile| {
+            for (col, row) in tile.pixels() {
+                let aov_pixel = self.sample_aovs_at(col, row, scene);
+                // SAFETY: tiles don't overlap and each is rendered by one thread
+                unsafe { aov_output.write(col, row, aov_pixel) };
+            }
+        });
+
+        self.aov_buffers(aov_pixels)
+    }
+
+    // adds samples to every pixel until it has `until` samples
+    fn render_pass(
+        &self,
+        scene: &(dyn Hittable + Sync),
+        accumulator: &mut Accumulator,
+        until: u32,
+        tracker: &Mutex<ProgressTrackerWrapper>,
+        progress_offset: usize,
+    ) {
+        let states = TileOutput::new(&mut accumulator.pixels, self.dimension.width);
+        self.run_tiles(tracker, progress_offset, |tile| {
+            for (col, row) in tile.pixels() {
+                // SAFETY: tiles don't overlap and each is rendered by one thread
+                unsafe {
+                    states.update(col, row, |state| {
+                        self.sample_pixel(col, row, scene, state, until)
+                    })
+                };
+            }
+        });
+    }
+
+    // Calls `render_tile` on every tile. The worker threads (one per core) keep pulling the next
+    // tile from a shared queue until there is none left or the time limit of the tracker is up.
+    // The progress counts the rendered pixels on top of `progress_offset`.
+    fn run_tiles(
+        &self,
+        tracker: &Mutex<ProgressTrackerWrapper>,
+        progress_offset: usize,
+        render_tile: impl Fn(&Tile) + Sync,
+    ) {
+        let concurrency_level: usize = thread::available_parallelism()
+            .unwrap_or(NonZeroUsize::new(1).unwrap())
+            .get();
+
+        let tiles = self.tiles();
+        let next_tile = AtomicUsize::new(0);
+        let completed = AtomicUsize::new(progress_offset);
+
+        thread::scope(|s| {
+            for _ in 0..concurrency_level {
+                s.spawn(|| {
+                    while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        if tracker.lock().unwrap().is_time_up() {
+                            break;
                         }
+                        render_tile(tile);
+
+                        // counted under the lock so the tracker only sees increasing counts
+                        let mut tracker = tracker.lock().unwrap();
+                        let pixel_count = (tile.width * tile.height) as usize;
+                        let done =
+                            completed.fetch_add(pixel_count, Ordering::Relaxed) + pixel_count;
+                        tracker.update_count(done);
                     }
-                    tx.send(SampleResult::None).unwrap();
                 });
             }
         });
+    }
 
-        let pixel_num = self.dimension.width as usize * self.dimension.height as usize;
-        let mut pixels = vec![Color::new([0.0, 0.0, 0.0]); pixel_num];
+    fn aov_buffers(&self, aov_pixels: Vec<AovPixel>) -> Option<AovBuffers> {
+        self.render_aovs
+            .then(|| AovBuffers::new(aov_pixels, self.dimension.clone()))
+    }
+
+    // splits the image into tiles of `tile_size` pixels in row-major order, the tiles on the right
+    // and bottom edges may be smaller
+    fn tiles(&self) -> Vec<Tile> {
+        let Dimension { width, height } = self.dimension;
+        let size = self.tile_size.max(1);
 
-        let mut completed_threads = 0usize;
-        while completed_threads < concurrency_level {
-            match rx.recv().unwrap() {
-                SampleResult::Color(index, color) => pixels[index] = color,
-                SampleResult::None => completed_threads += 1,
+        (0..height)
+            .step_by(size as usize)
+            .flat_map(|row| {
+                (0..width).step_by(size as usize).map(move |col| Tile {
+                    col,
+                    row,
+                    width: size.min(width - col),
+                    height: size.min(height - row),
+                })
+            })
+            .collect()
+    }
+
+    // returns the color and the number of samples taken
+    fn sample_color_at(&self, col: u32, row: u32, hittable: &dyn Hittable) -> (Color, u32) {
+        let mut state = PixelState::default();
+        self.sample_pixel(col, row, hittable, &mut state, self.sampling_rate);
+        (state.color(), state.sample_count())
+    }
+
+    // adds samples to the pixel until it has `until` samples or it converged
+    fn sample_pixel(
+        &self,
+        col: u32,
+        row: u32,
+        hittable: &dyn Hittable,
+        state: &mut PixelState,
+        until: u32,
+    ) {
+        let pixel_center = self.pixel_center(col, row);
+
+        // every sample has its own random stream, so a pixel doesn't depend on the thread or the
+        // order it's rendered in
+        let pixel_index = row as u64 * self.dimension.width as u64 + col as u64;
+        while state.sample_count() < until && !state.converged {
+            let sample = state.sample_count() as u64;
+            util::seed_rng(util::derive_seed(self.seed, &[pixel_index, sample]));
+            let ray = self.get_ray(pixel_center);
+            let color = self.ray_color(ray, self.max_depth, hittable, None);
+            state.luminance.push(color.luminance());
+            state.sum = state.sum.clone() + color;
+
+            if let Some(adaptive) = &self.adaptive {
+                state.converged = state.sample_count() >= adaptive.min_samples
+                    && state.luminance.relative_error() < adaptive.threshold;
             }
         }
+    }
 
-        Image {
-            pixels,
-            dimension: self.dimension.clone(),
+    // First hits of the pixel samples. Uses the same random streams as `sample_color_at`, so the
+    // camera rays are the same as the ones of the color samples. The first hits converge much
+    // faster than the color, so at most MAX_AOV_SAMPLES samples are taken.
+    fn sample_aovs_at(&self, col: u32, row: u32, hittable: &dyn Hittable) -> AovPixel {
+        const MAX_AOV_SAMPLES: u32 = 64;
+        let sample_count = self.sampling_rate.min(MAX_AOV_SAMPLES);
+
+        let mut pixel = AovPixel::default();
+        let mut depth = 0.0;
+        let mut hit_count = 0;
+        let pixel_center = self.pixel_center(col, row);
+
+        let pixel_index = row as u64 * self.dimension.width as u64 + col as u64;
+        for sample in 0..sample_count {
+            util::seed_rng(util::derive_seed(self.seed, &[pixel_index, sample as u64]));
+            let ray = self.get_ray(pixel_center);
+
+            match hittable.hit(ray.clone(), Interval::new(0.001, f64::INFINITY)) {
+                Some(HitResult { record, material }) => {
+                    let albedo = match material {
+                        Some(material) => material.albedo(&record),
+                        None => Color::from(record.normal * 0.5 + 0.5),
+                    };
+                    pixel.albedo = pixel.albedo + albedo;
+                    pixel.normal = pixel.normal + record.normal;
+                    pixel.position = pixel.position + record.point;
+                    depth += (self.camera.position - record.point).dot(self.camera.view_dir);
+                    hit_count += 1;
+
+                    if sample == 0 {
+                        pixel.id = aov::material_key(material);
+                    }
+                }
+                None => {
+                    let background = self.background.value(&ray);
+                    pixel.albedo = pixel.albedo + background.clamp(Interval::new(0.0, 1.0));
+                }
+            }
         }
+
+        // the albedo and normal fade out on the edges of objects like the color does, the position
+        // and depth are averaged over the samples that hit something
+        pixel.albedo = pixel.albedo / sample_count as f64;
+        pixel.normal = pixel.normal / sample_count as f64;
+        if hit_count > 0 {
+            pixel.position = pixel.position / hit_count as f64;
+            pixel.depth = depth / hit_count as f64;
+        }
+        pixel
     }
 
-    fn sample_color_at(&self, col: u32, row: u32, hittable: &dyn Hittable) -> Color {
-        let mut accumulated_color = Color::new_one(0.0);
+    // average number of bvh nodes visited by a camera ray (one per pixel), bounces are not traced
+    pub fn average_bvh_visits(&self, hittable: &dyn Hittable) -> f64 {
+        let Dimension { width, height } = self.dimension;
 
-        let pixel_center = self.viewport.pixel_origin
-            + (self.viewport.du_vector * col as f64)
-            + (self.viewport.dv_vector * row as f64);
+        let mut visits = 0;
+        for row in 0..height {
+            for col in 0..width {
+                let ray = self.get_ray(self.pixel_center(col, row));
+                hittable.hit_counted(ray, Interval::new(0.001, f64::INFINITY), &mut visits);
+            }
+        }
 
-        for _ in 0..self.sampling_rate {
-            let pixel_sample = pixel_center + self.sample_unit_square();
-            let ray_origin = match self.camera.defocus_angle {
-                x if x <= 0.0 => self.camera.position,
-                _ => self.defocus_disk_sample(),
-            };
-            let ray_direction = pixel_sample - ray_origin;
-            let ray_time = util::get_random_canonical();
+        visits as f64 / (width as f64 * height as f64)
+    }
 
-            let ray = Ray3 {
-                origin: ray_origin,
-                direction: ray_direction.unit_vector(),
-                time: ray_time,
-            };
+    fn pixel_center(&self, col: u32, row: u32) -> Vec3 {
+        self.viewport.pixel_origin
+            + (self.viewport.du_vector * col as f64)
+            + (self.viewport.dv_vector * row as f64)
+    }
 
-            accumulated_color = accumulated_color + self.ray_color(ray, self.max_depth, hittable);
-        }
+    // random ray going through the pixel area
+    fn get_ray(&self, pixel_center: Vec3) -> Ray3 {
+        let pixel_sample = pixel_center + self.sample_unit_square();
+        let ray_origin = match self.camera.defocus_angle {
+            x if x <= 0.0 => self.camera.position,
+            _ => self.defocus_disk_sample(),
+        };
+        let ray_direction = pixel_sample - ray_origin;
+        let ray_time = util::get_random_canonical();
 
-        accumulated_color / self.sampling_rate as f64
+        Ray3 {
+            origin: ray_origin,
+            direction: ray_direction.unit_vector(),
+            time: ray_time,
+        }
     }
 
-    fn ray_color(&self, ray: Ray3, depth: u32, hittable: &dyn Hittable) -> Color {
+    // `scatter_pdf` is the density of the direction of `ray` if it was scattered by a diffuse
+    // material, the light it hits is then weighted against sampling the lights directly
+    fn ray_color(
+        &self,
+        ray: Ray3,
+        depth: u32,
+        hittable: &dyn Hittable,
+        scatter_pdf: Option<f64>,
+    ) -> Color {
         if depth <= 0 {
             return Color::new_one(0.0);
         }
 
         match hittable.hit(ray.clone(), Interval::new(0.001, f64::INFINITY)) {
-            Some(HitResult { record, material }) => {
-                let normal = record.normal.clone();
-                match material.and_then(|v| v.scatter(ray, record)) {
+            Some(HitResult {
+                record,
+                material: Some(material),
+            }) => {
+                let mut emitted = material.emitted(&record);
+                let is_emissive = emitted != Color::new_one(0.0);
+                if let (Some(scatter_pdf), true) = (scatter_pdf, is_emissive) {
+                    let light_pdf = self.lights.pdf_value(ray.origin, ray.direction);
+                    emitted = emitted * power_heuristic(scatter_pdf, light_pdf);
+                }
+
+                let time = ray.time;
+                match material.scatter(ray, record.clone()) {
                     Some(ScatterResult {
                         ray: new_ray,
                         attenuation,
-                    }) => attenuation * self.ray_color(new_ray, depth - 1, hittable),
-                    None => Color::from(normal * 0.5 + 0.5),
-                }
-            }
-            None => {
-                // missed, use background color instead
-                let direction = ray.direction.unit_vector();
+                    }) => {
+                        let new_pdf = match self.lights.is_empty() {
+                            true => None,
+                            false => material.scattering_pdf(&record, new_ray.direction),
+                        };
 
-                // lerp
-                let a = 0.5 * (direction.data[1] + 1.0);
-                let white = Color::new_one(1.0);
-                let blue = Color::new([0.5, 0.7, 1.0]);
+                        // the light is sampled only if the scattered ray can still hit it, so
+                        // both ways see the same lights
+                        let direct = match (new_pdf, depth > 1) {
+                            (Some(_), true) => {
+                                self.sample_light(&record, material, time, hittable)
+                                    * attenuation.clone()
+                            }
+                            _ => Color::new_one(0.0),
+                        };
 
-                white * (1.0 - a) + blue * a
+                        let indirect = self.ray_color(new_ray, depth - 1, hittable, new_pdf);
+                        emitted + direct + attenuation * indirect
+                    }
+                    None => emitted,
+                }
             }
+            // object without material, show its normal instead
+            Some(HitResult {
+                record,
+                material: None,
+            }) => Color::from(record.normal * 0.5 + 0.5),
+            // missed, use background color instead
+            None => self.background.value(&ray),
         }
     }
 
+    // Next event estimation: the light reaching the hit point from a random point of a light,
+    // divided by the attenuation. The scattered ray may hit the lights as well, both are combined
+    // with multiple importance sampling (Veach's power heuristic).
+    fn sample_light(
+        &self,
+        record: &HitRecord,
+        material: &dyn Material,
+        time: f64,
+        hittable: &dyn Hittable,
+    ) -> Color {
+        let direction = self.lights.random_direction(record.point);
+        let light_pdf = self.lights.pdf_value(record.point, direction);
+        let scatter_pdf = material.scattering_pdf(record, direction).unwrap_or(0.0);
+        if light_pdf <= 0.0 || !light_pdf.is_finite() || scatter_pdf <= 0.0 {
+            return Color::new_one(0.0);
+        }
+
+        // the shadow ray gets the light of whatever it hits first, nothing if it's blocked
+        let shadow_ray = Ray3 {
+            origin: record.point,
+            direction,
+            time,
+        };
+        let emitted = match hittable.hit(shadow_ray, Interval::new(0.001, f64::INFINITY)) {
+            Some(HitResult {
+                record,
+                material: Some(material),
+            }) => material.emitted(&record),
+            _ => return Color::new_one(0.0),
+        };
+
+        emitted * (scatter_pdf * power_heuristic(light_pdf, scatter_pdf) / light_pdf)
+    }
+
     fn sample_unit_square(&self) -> Vec3 {
         let px = util::get_random(0.0, 1.0) - 0.5;
         let py = util::get_random(0.0, 1.0) - 0.5;
@@ -315,10 +774,246 @@ impl Default for TracerParams {
             focus_distance: 10.0,
             look_from: Vector::new([13.0, 2.0, 3.0]),
             look_at: Vector::new([0.0, 0.0, 0.0]),
+            background: Background::sky(),
+            tile_size: 16,
+            seed: 0,
+            render_aovs: false,
+            adaptive: None,
+            lights: HittableList::new(),
         }
     }
 }
 
+// weight of a sample taken with the density `pdf` when `other_pdf` could have taken it too
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    match a + b > 0.0 {
+        true => a / (a + b),
+        false => 0.0,
+    }
+}
+
+// running mean and variance (Welford's algorithm)
+#[derive(Clone, Debug, Default)]
+struct RunningStats {
+    count: u32,
+    mean: f64,
+    // sum of the squared differences from the mean
+    m2: f64,
+}
+
+impl RunningStats {
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    // standard error of the mean relative to the mean, very dark pixels are compared to
+    // MIN_LUMINANCE instead so they don't take forever to converge
+    fn relative_error(&self) -> f64 {
+        const MIN_LUMINANCE: f64 = 0.01;
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        (variance / self.count as f64).sqrt() / self.mean.abs().max(MIN_LUMINANCE)
+    }
+}
+
+// rectangular region of the image, in pixels
+#[derive(Clone, Debug)]
+struct Tile {
+    col: u32,
+    row: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Tile {
+    // (col, row) of every pixel in the tile, row by row
+    fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.row..self.row + self.height)
+            .flat_map(move |row| (self.col..self.col + self.width).map(move |col| (col, row)))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ProgressiveParams {
+    // samples added to every pixel in each pass
+    pub samples_per_pass: u32,
+    // minimum time between two snapshots
+    pub snapshot_interval: Duration,
+    // no new pass is started once the time is used up
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for ProgressiveParams {
+    fn default() -> Self {
+        Self {
+            samples_per_pass: 4,
+            snapshot_interval: Duration::from_secs(30),
+            time_limit: None,
+        }
+    }
+}
+
+// running estimate of a pixel
+#[derive(Clone, Debug)]
+struct PixelState {
+    sum: Color,
+    luminance: RunningStats,
+    // with adaptive sampling, no more samples are needed
+    converged: bool,
+}
+
+impl Default for PixelState {
+    fn default() -> Self {
+        Self {
+            sum: Color::new_one(0.0),
+            luminance: RunningStats::default(),
+            converged: false,
+        }
+    }
+}
+
+impl PixelState {
+    fn sample_count(&self) -> u32 {
+        self.luminance.count
+    }
+
+    fn color(&self) -> Color {
+        self.sum.clone() / self.sample_count().max(1) as f64
+    }
+}
+
+// running estimate of the whole image for progressive rendering
+pub struct Accumulator {
+    pixels: Vec<PixelState>,
+    aovs: Option<AovBuffers>,
+    dimension: Dimension,
+}
+
+impl Accumulator {
+    pub fn image(&self) -> Image {
+        Image {
+            pixels: self.pixels.iter().map(PixelState::color).collect(),
+            dimension: self.dimension.clone(),
+            aovs: self.aovs.clone(),
+            sample_counts: Some(self.pixels.iter().map(PixelState::sample_count).collect()),
+        }
+    }
+
+    pub fn dimension(&self) -> &Dimension {
+        &self.dimension
+    }
+
+    // fewest samples of the pixels that are not converged yet, none if nothing is left to sample
+    pub fn min_sample_count(&self) -> Option<u32> {
+        self.pixels
+            .iter()
+            .filter(|p| !p.converged)
+            .map(PixelState::sample_count)
+            .min()
+    }
+
+    // Binary dump of the estimate (little endian): the dimension, then for each pixel the sum of
+    // its samples, the sample count, the running mean and m2 of the luminance and the converged
+    // flag. The aovs are not stored, they are cheap to render again.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.dimension.width.to_le_bytes())?;
+        writer.write_all(&self.dimension.height.to_le_bytes())?;
+        for pixel in &self.pixels {
+            for value in [pixel.sum.r(), pixel.sum.g(), pixel.sum.b()] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&pixel.luminance.count.to_le_bytes())?;
+            writer.write_all(&pixel.luminance.mean.to_le_bytes())?;
+            writer.write_all(&pixel.luminance.m2.to_le_bytes())?;
+            writer.write_all(&[pixel.converged as u8])?;
+        }
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        }
+        fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(f64::from_le_bytes(bytes))
+        }
+
+        let dimension = Dimension {
+            width: read_u32(reader)?,
+            height: read_u32(reader)?,
+        };
+        let pixels = (0..dimension.width as usize * dimension.height as usize)
+            .map(|_| {
+                let sum = Color::new([read_f64(reader)?, read_f64(reader)?, read_f64(reader)?]);
+                let luminance = RunningStats {
+                    count: read_u32(reader)?,
+                    mean: read_f64(reader)?,
+                    m2: read_f64(reader)?,
+                };
+                let mut converged = [0];
+                reader.read_exact(&mut converged)?;
+                Ok(PixelState {
+                    sum,
+                    luminance,
+                    converged: converged[0] != 0,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            pixels,
+            aovs: None,
+            dimension,
+        })
+    }
+}
+
+// Pixel buffer the render threads write into directly, without locking. Writing is unsafe since
+// the threads must only write to pixels no other thread writes to (i.e. their own tiles).
+struct TileOutput<'a, T> {
+    pixels: *mut T,
+    width: u32,
+    len: usize,
+    _marker: PhantomData<&'a mut [T]>,
+}
+
+unsafe impl<T: Send> Sync for TileOutput<'_, T> {}
+
+impl<'a, T> TileOutput<'a, T> {
+    fn new(pixels: &'a mut [T], width: u32) -> Self {
+        Self {
+            pixels: pixels.as_mut_ptr(),
+            width,
+            len: pixels.len(),
+            _marker: PhantomData,
+        }
+    }
+
+    // SAFETY: no other thread may access the pixel at the same time
+    unsafe fn update(&self, col: u32, row: u32, f: impl FnOnce(&mut T)) {
+        let index = row as usize * self.width as usize + col as usize;
+        assert!(index < self.len);
+        f(&mut *self.pixels.add(index));
+    }
+
+    // SAFETY: no other thread may access the pixel at the same time
+    unsafe fn write(&self, col: u32, row: u32, value: T) {
+        let index = row as usize * self.width as usize + col as usize;
+        assert!(index < self.len);
+        *self.pixels.add(index) = value;
+    }
+}
+
 struct ProgressTrackerWrapper {
     tracker: ProgressTracker,
     min_update_interval: usize,
@@ -327,31 +1022,357 @@ struct ProgressTrackerWrapper {
 
 impl ProgressTrackerWrapper {
     pub fn new(width: u32, steps: usize) -> Self {
-        const MINIMUM_UPDATE_INTERVAL: usize = 512;
         let max_count = steps * width as usize;
+        Self::with_tracker(width, ProgressTracker::new(0, max_count as isize))
+    }
+
+    // for work that only stops with the time limit
+    pub fn unbounded(width: u32) -> Self {
+        Self::with_tracker(width, ProgressTracker::unbounded(0))
+    }
+
+    fn with_tracker(width: u32, tracker: ProgressTracker) -> Self {
+        const MINIMUM_UPDATE_INTERVAL: usize = 512;
         Self {
-            tracker: ProgressTracker::new(0, max_count as isize),
+            tracker,
             // min_update_interval: MINIMUM_UPDATE_INTERVAL.min(width as usize),
             min_update_interval: MINIMUM_UPDATE_INTERVAL,
             width: width as usize,
         }
     }
 
+    pub fn with_time_limit(mut self, time_limit: Option<Duration>) -> Self {
+        self.tracker = self.tracker.with_time_limit(time_limit);
+        self
+    }
+
+    pub fn is_time_up(&self) -> bool {
+        self.tracker.is_time_up()
+    }
+
     pub fn update(&mut self, count: usize, width_step: usize) {
         let new_count = count * self.width + width_step;
         let should_update = new_count % self.min_update_interval == 0;
-        let reached_max = new_count == self.tracker.max() as usize;
+        let reached_max = self.tracker.max() == Some(new_count as isize);
         if should_update || reached_max {
-            self.tracker.update(new_count as isize);
-            eprint!(
-                "Progress: {:>6.2}% | Elapsed: {:>6.2}s | ETA: {:>6.2}s\r",
-                self.tracker.progress(),
-                self.tracker.get_elapsed().as_secs_f64(),
-                self.tracker.get_eta().as_secs_f64()
-            );
-            if reached_max {
-                eprintln!();
+            self.update_count(new_count);
+        }
+    }
+
+    // reports the progress right away, for callers that already update sparsely (e.g. per tile)
+    pub fn update_count(&mut self, new_count: usize) {
+        let reached_max = self.tracker.max() == Some(new_count as isize);
+        self.tracker.update(new_count as isize);
+        eprint!(
+            "Progress: {:>6.2}% | Elapsed: {:>6.2}s | ETA: {:>6.2}s\r",
+            self.tracker.progress(),
+            self.tracker.get_elapsed().as_secs_f64(),
+            self.tracker.get_eta().as_secs_f64()
+        );
+        if reached_max {
+            eprintln!();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes::{SceneOptions, SCENES};
+
+    #[test]
+    fn test_tiles() {
+        for tile_size in [1, 7, 16, 1000] {
+            let ray_tracer = RayTracer::new(TracerParams {
+                height: 45,
+                tile_size,
+                ..Default::default()
+            });
+            let Dimension { width, height } = ray_tracer.dimension;
+
+            // every pixel is covered by exactly one tile
+            let mut covered = vec![0; (width * height) as usize];
+            for tile in ray_tracer.tiles() {
+                assert!(tile.width <= tile_size && tile.height <= tile_size);
+                for row in tile.row..tile.row + tile.height {
+                    for col in tile.col..tile.col + tile.width {
+                        covered[(row * width + col) as usize] += 1;
+                    }
+                }
             }
+            assert!(covered.iter().all(|&c| c == 1));
         }
     }
+
+    #[test]
+    fn test_deterministic() {
+        let params = |tile_size| TracerParams {
+            height: 18,
+            sampling_rate: 4,
+            tile_size,
+            seed: 7,
+            ..Default::default()
+        };
+        let options = SceneOptions {
+            seed: 7,
+            ..Default::default()
+        };
+
+        // scene generation, thread count and tile order don't change the result
+        let world = SCENES["random-spheres-bouncing"](&options).world;
+        let expected = RayTracer::new(params(16)).render(&world).pixels;
+        for tile_size in [1, 5, 16] {
+            let world = SCENES["random-spheres-bouncing"](&options).world;
+            let pixels = RayTracer::new(params(tile_size))
+                .render_multi(&world)
+                .pixels;
+            assert_eq!(expected, pixels);
+        }
+    }
+
+    #[test]
+    fn test_aovs() {
+        use crate::hittable::{HittableList, Sphere};
+        use crate::material::Lambertian;
+
+        // a sphere straight ahead of the camera, its front is 9 units away
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            Vec3::new([0.0, 0.0, 0.0]),
+            1.0,
+            Some(Box::new(Lambertian::new(Color::new([0.2, 0.4, 0.6])))),
+        )));
+        let params = || TracerParams {
+            height: 21,
+            aspect_ratio: 1.0,
+            sampling_rate: 4,
+            defocus_angle: 0.0,
+            look_from: Vec3::new([0.0, 0.0, 10.0]),
+            render_aovs: true,
+            ..Default::default()
+        };
+
+        let image = RayTracer::new(params()).render(&world);
+        let aovs = image.aovs.unwrap();
+        let center = &aovs.pixels[10 * 21 + 10];
+        assert!((center.depth - 9.0).abs() < 1e-2);
+        assert!((center.normal - Vec3::new([0.0, 0.0, 1.0])).length() < 0.1);
+        assert_eq!(center.albedo, Color::new([0.2, 0.4, 0.6]));
+        assert_eq!(center.id, 1);
+
+        let corner = &aovs.pixels[0];
+        assert_eq!(corner.id, 0);
+        assert!(corner.depth.is_infinite());
+
+        let multi = RayTracer::new(params()).render_multi(&world);
+        assert_eq!(aovs.pixels, multi.aovs.unwrap().pixels);
+        assert!(RayTracer::new(TracerParams::default())
+            .aov_buffers(Vec::new())
+            .is_none());
+    }
+
+    #[test]
+    fn test_adaptive() {
+        let mut stats = RunningStats::default();
+        for value in [1.0, 2.0, 3.0, 4.0] {
+            stats.push(value);
+        }
+        assert_eq!(stats.mean, 2.5);
+        // sample variance is 5/3, the standard error is sqrt(5/12)
+        assert!((stats.relative_error() - (5.0f64 / 12.0).sqrt() / 2.5).abs() < 1e-12);
+
+        // nothing to hit, every pixel sees the same sky and stops as early as possible
+        let world = crate::hittable::HittableList::new();
+        let params = |adaptive| TracerParams {
+            height: 9,
+            sampling_rate: 32,
+            adaptive,
+            ..Default::default()
+        };
+        let adaptive = AdaptiveSampling {
+            min_samples: 4,
+            threshold: 0.01,
+        };
+
+        let image = RayTracer::new(params(Some(adaptive))).render_multi(&world);
+        assert!(image.sample_counts.unwrap().iter().all(|&c| c == 4));
+        let image = RayTracer::new(params(None)).render(&world);
+        assert!(image.sample_counts.unwrap().iter().all(|&c| c == 32));
+    }
+
+    #[test]
+    fn test_light_sampling() {
+        let options = SceneOptions::default();
+        let params = |sampling_rate, lights| TracerParams {
+            height: 8,
+            aspect_ratio: 1.0,
+            sampling_rate,
+            vfov: 40.0,
+            defocus_angle: 0.0,
+            look_from: Vec3::new([278.0, 278.0, -800.0]),
+            look_at: Vec3::new([278.0, 278.0, 0.0]),
+            lights,
+            ..Default::default()
+        };
+        let render = |sampling_rate, light_sampling| {
+            let scene = SCENES["cornell-box"](&options);
+            let lights = match light_sampling {
+                true => scene.lights,
+                false => HittableList::new(),
+            };
+            RayTracer::new(params(sampling_rate, lights))
+                .render(&scene.world)
+                .pixels
+        };
+        let mean = |pixels: &[Color]| pixels.iter().map(Color::luminance).sum::<f64>();
+        let error = |pixels: &[Color], expected: &[Color]| {
+            pixels
+                .iter()
+                .zip(expected)
+                .map(|(a, b)| (a.luminance() - b.luminance()).powi(2))
+                .sum::<f64>()
+        };
+
+        let expected = render(256, true);
+        let with_lights = render(16, true);
+        let without_lights = render(16, false);
+
+        // same image, with much less noise
+        let relative = (mean(&with_lights) - mean(&expected)).abs() / mean(&expected);
+        assert!(relative < 0.05, "{}", relative);
+        let (with_error, without_error) = (
+            error(&with_lights, &expected),
+            error(&without_lights, &expected),
+        );
+        assert!(
+            with_error * 2.0 < without_error,
+            "{} {}",
+            with_error,
+            without_error
+        );
+    }
+
+    #[test]
+    fn test_progressive() {
+        let options = SceneOptions::default();
+        let world = SCENES["random-spheres-bouncing"](&options).world;
+        let params = |adaptive| TracerParams {
+            height: 18,
+            sampling_rate: 10,
+            adaptive,
+            ..Default::default()
+        };
+        let progressive = ProgressiveParams {
+            samples_per_pass: 3,
+            snapshot_interval: Duration::ZERO,
+            time_limit: None,
+        };
+
+        // same samples as a single pass render, in a few passes with a snapshot after each of them
+        // but the last one
+        let adaptive = AdaptiveSampling {
+            min_samples: 2,
+            threshold: 0.05,
+        };
+        for adaptive in [None, Some(adaptive)] {
+            let ray_tracer = RayTracer::new(params(adaptive));
+            let expected = ray_tracer.render_multi(&world);
+
+            let mut snapshots = Vec::new();
+            let image = ray_tracer.render_progressive(&world, &progressive, None, |accumulator| {
+                snapshots.push(accumulator.image().sample_counts.unwrap())
+            });
+            assert_eq!(image.pixels, expected.pixels);
+            assert_eq!(image.sample_counts, expected.sample_counts);
+            assert_eq!(snapshots.len(), 4);
+            assert!(snapshots[1].iter().all(|&c| c <= 6));
+        }
+    }
+
+    #[test]
+    fn test_resume_converged() {
+        // nothing to hit, every pixel converges after its first samples
+        let world = crate::hittable::HittableList::new();
+        let ray_tracer = RayTracer::new(TracerParams {
+            height: 9,
+            sampling_rate: 32,
+            adaptive: Some(AdaptiveSampling {
+                min_samples: 4,
+                threshold: 0.01,
+            }),
+            ..Default::default()
+        });
+        let progressive = ProgressiveParams {
+            samples_per_pass: 4,
+            snapshot_interval: Duration::ZERO,
+            time_limit: None,
+        };
+
+        let mut checkpoint = Vec::new();
+        ray_tracer.render_progressive(&world, &progressive, None, |accumulator| {
+            checkpoint.clear();
+            accumulator.write(&mut checkpoint).unwrap();
+        });
+        let finished = Accumulator::read(&mut checkpoint.as_slice()).unwrap();
+        assert_eq!(finished.min_sample_count(), None);
+
+        // the passes are not run again, only the final snapshot is taken
+        let mut snapshots = 0;
+        let image =
+            ray_tracer.render_progressive(&world, &progressive, Some(finished), |_| snapshots += 1);
+        assert_eq!(snapshots, 1);
+        assert!(image.sample_counts.unwrap().iter().all(|&c| c == 4));
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let options = SceneOptions::default();
+        let world = SCENES["random-spheres-bouncing"](&options).world;
+        let ray_tracer = RayTracer::new(TracerParams {
+            height: 9,
+            sampling_rate: 1,
+            ..Default::default()
+        });
+        let progressive = ProgressiveParams {
+            samples_per_pass: 1,
+            time_limit: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let image = ray_tracer.render_progressive(&world, &progressive, None, |_| {});
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // the tiles of the last pass may not have been rendered
+        let counts = image.sample_counts.unwrap();
+        let min = *counts.iter().min().unwrap();
+        // the sampling rate is ignored with a time limit
+        assert!(min > 1);
+        assert!(counts.iter().all(|&c| c == min || c == min + 1));
+
+        // nothing to hit, the pixels converge long before the time is up
+        let ray_tracer = RayTracer::new(TracerParams {
+            height: 9,
+            sampling_rate: 1,
+            adaptive: Some(AdaptiveSampling {
+                min_samples: 4,
+                threshold: 0.01,
+            }),
+            ..Default::default()
+        });
+        let progressive = ProgressiveParams {
+            time_limit: Some(Duration::from_secs(10)),
+            ..progressive
+        };
+
+        let start = Instant::now();
+        let image = ray_tracer.render_progressive(
+            &crate::hittable::HittableList::new(),
+            &progressive,
+            None,
+            |_| {},
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(image.sample_counts.unwrap().iter().all(|&c| c == 4));
+    }
 }
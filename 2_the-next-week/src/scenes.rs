@@ -1,16 +1,46 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 use lazy_static::lazy_static;
 
-use crate::bvh::BvhNode;
+use crate::bvh::{BvhNode, BvhParams};
 use crate::color::Color;
-use crate::hittable::{Hittable, HittableList, Sphere};
-use crate::material::{Dielectric, Lambertian, Material, Metal};
-use crate::texture::CheckerTexture;
+use crate::hittable::{make_box, Hittable, HittableList, Quad, Sphere, Triangle};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::medium::ConstantMedium;
+use crate::mesh::{MeshBuffers, TriangleMesh};
+use crate::ray_tracer::Background;
+use crate::texture::{CheckerTexture, ColorRamp, ImageTexture, MarbleTexture, NoiseTexture};
+use crate::transform::{Instance, Transform};
 use crate::vec::Vector;
 use crate::{util, vec};
 
-type Function = fn() -> HittableList;
+pub struct Scene {
+    pub world: HittableList,
+    pub background: Background,
+    // copies of the emissive objects of the world, sampled directly by the renderer
+    pub lights: HittableList,
+}
+
+impl Scene {
+    pub fn with_sky(world: HittableList) -> Self {
+        Self {
+            world,
+            background: Background::sky(),
+            lights: HittableList::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SceneOptions {
+    pub bvh: BvhParams,
+    // scenes with random objects are the same every time they're built with the same seed
+    pub seed: u64,
+}
+
+type Function = fn(&SceneOptions) -> Scene;
 lazy_static! {
     pub static ref SCENES: HashMap<&'static str, Function> = vec![
         (
@@ -21,13 +51,23 @@ lazy_static! {
             "random-spheres-bouncing",
             ray_tracing_in_one_week_book_scene_modified_bvh as Function,
         ),
-        ("checkered-spheres", checkered_spheres as Function,)
+        ("checkered-spheres", checkered_spheres as Function,),
+        ("quads", quads as Function,),
+        ("triangles", triangles as Function,),
+        ("simple-light", simple_light as Function,),
+        ("cornell-box", cornell_box as Function,),
+        ("cornell-smoke", cornell_smoke as Function,),
+        ("final-scene", final_scene as Function,),
+        ("earth", earth as Function,),
+        ("perlin-spheres", perlin_spheres as Function,),
+        ("instancing", instancing as Function,)
     ]
     .into_iter()
     .collect();
 }
 
-pub fn ray_tracing_in_one_week_book_scene() -> HittableList {
+pub fn ray_tracing_in_one_week_book_scene(options: &SceneOptions) -> Scene {
+    util::seed_rng(options.seed);
     let mut scene = HittableList::new();
 
     // ground
@@ -87,10 +127,11 @@ pub fn ray_tracing_in_one_week_book_scene() -> HittableList {
         Some(Box::new(Metal::new(Color::new([0.7, 0.6, 0.5]), 0.0))), // shiny
     )));
 
-    scene
+    Scene::with_sky(scene)
 }
 
-fn ray_tracing_in_one_week_book_scene_modified() -> Vec<Box<dyn Hittable>> {
+fn ray_tracing_in_one_week_book_scene_modified(seed: u64) -> Vec<Box<dyn Hittable>> {
+    util::seed_rng(seed);
     let mut objects = Vec::<Box<dyn Hittable>>::new();
 
     let checker = Box::new(CheckerTexture::from_color(
@@ -166,22 +207,22 @@ fn ray_tracing_in_one_week_book_scene_modified() -> Vec<Box<dyn Hittable>> {
     objects
 }
 
-pub fn ray_tracing_in_one_week_book_scene_modified_simple() -> HittableList {
+pub fn ray_tracing_in_one_week_book_scene_modified_simple(options: &SceneOptions) -> Scene {
     let mut list = HittableList::new();
-    ray_tracing_in_one_week_book_scene_modified()
+    ray_tracing_in_one_week_book_scene_modified(options.seed)
         .into_iter()
         .for_each(|o| list.add(o));
-    list
+    Scene::with_sky(list)
 }
 
-pub fn ray_tracing_in_one_week_book_scene_modified_bvh() -> HittableList {
+pub fn ray_tracing_in_one_week_book_scene_modified_bvh(options: &SceneOptions) -> Scene {
     let mut list = HittableList::new();
-    let objects = ray_tracing_in_one_week_book_scene_modified();
-    list.add(Box::new(BvhNode::new(objects)));
-    list
+    let objects = ray_tracing_in_one_week_book_scene_modified(options.seed);
+    list.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+    Scene::with_sky(list)
 }
 
-pub fn checkered_spheres() -> HittableList {
+pub fn checkered_spheres(options: &SceneOptions) -> Scene {
     let mut objects = Vec::<Box<dyn Hittable>>::new();
 
     // I don't want to go into the trouble implementing clone for dyn Texture
@@ -208,7 +249,557 @@ pub fn checkered_spheres() -> HittableList {
     )));
 
     let mut scene = HittableList::new();
-    scene.add(Box::new(BvhNode::new(objects)));
+    scene.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+
+    Scene::with_sky(scene)
+}
+
+// best viewed from "0.0/0.0/9.0" with vfov of 80
+pub fn quads(options: &SceneOptions) -> Scene {
+    let objects: Vec<Box<dyn Hittable>> = vec![
+        // left
+        Box::new(Quad::new(
+            Vector::new([-3.0, -2.0, 5.0]),
+            Vector::new([0.0, 0.0, -4.0]),
+            Vector::new([0.0, 4.0, 0.0]),
+            Some(Box::new(Lambertian::new(Color::new([1.0, 0.2, 0.2])))),
+        )),
+        // back
+        Box::new(Quad::new(
+            Vector::new([-2.0, -2.0, 0.0]),
+            Vector::new([4.0, 0.0, 0.0]),
+            Vector::new([0.0, 4.0, 0.0]),
+            Some(Box::new(Lambertian::new(Color::new([0.2, 1.0, 0.2])))),
+        )),
+        // right
+        Box::new(Quad::new(
+            Vector::new([3.0, -2.0, 1.0]),
+            Vector::new([0.0, 0.0, 4.0]),
+            Vector::new([0.0, 4.0, 0.0]),
+            Some(Box::new(Lambertian::new(Color::new([0.2, 0.2, 1.0])))),
+        )),
+        // upper
+        Box::new(Quad::new(
+            Vector::new([-2.0, 3.0, 1.0]),
+            Vector::new([4.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, 4.0]),
+            Some(Box::new(Lambertian::new(Color::new([1.0, 0.5, 0.0])))),
+        )),
+        // lower
+        Box::new(Quad::new(
+            Vector::new([-2.0, -3.0, 5.0]),
+            Vector::new([4.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, -4.0]),
+            Some(Box::new(Lambertian::new(Color::new([0.2, 0.8, 0.8])))),
+        )),
+    ];
+
+    let mut scene = HittableList::new();
+    scene.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+
+    Scene::with_sky(scene)
+}
+
+pub fn triangles(options: &SceneOptions) -> Scene {
+    let mut objects = Vec::<Box<dyn Hittable>>::new();
+
+    let checker = Box::new(CheckerTexture::from_color(
+        0.32,
+        Color::new([0.2, 0.3, 0.1]),
+        Color::new([0.9, 0.9, 0.9]),
+    ));
+
+    // ground
+    objects.push(Box::new(Sphere::new(
+        Vector::new([0.0, -1000.0, 0.0]),
+        1000.0,
+        Some(Box::new(Lambertian::with_texture(checker))),
+    )));
+
+    // flat shaded single triangle
+    objects.push(Box::new(Triangle::new(
+        [
+            Vector::new([-4.0, 0.0, -1.0]),
+            Vector::new([-2.0, 0.0, 1.0]),
+            Vector::new([-3.0, 2.5, 0.0]),
+        ],
+        Some(Box::new(Lambertian::new(Color::new([0.8, 0.3, 0.1])))),
+    )));
+
+    // octahedron, smooth shaded by using the vertex positions as normals
+    let positions = vec![
+        Vector::new([1.0, 0.0, 0.0]),
+        Vector::new([-1.0, 0.0, 0.0]),
+        Vector::new([0.0, 1.0, 0.0]),
+        Vector::new([0.0, -1.0, 0.0]),
+        Vector::new([0.0, 0.0, 1.0]),
+        Vector::new([0.0, 0.0, -1.0]),
+    ];
+    let normals = positions.clone();
+    let octahedron = MeshBuffers {
+        positions: positions
+            .into_iter()
+            .map(|p| p + Vector::new([0.0, 1.0, 0.0]))
+            .collect(),
+        normals: Some(normals),
+        uvs: None,
+        indices: vec![
+            [0, 2, 4],
+            [4, 2, 1],
+            [1, 2, 5],
+            [5, 2, 0],
+            [4, 3, 0],
+            [1, 3, 4],
+            [5, 3, 1],
+            [0, 3, 5],
+        ],
+        material: Some(Box::new(Metal::new(Color::new([0.7, 0.6, 0.5]), 0.1))),
+    };
+    objects.push(Box::new(TriangleMesh::new(octahedron)));
+
+    let mut scene = HittableList::new();
+    scene.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+
+    Scene::with_sky(scene)
+}
+
+// best viewed from "26.0/3.0/6.0" looking at "0.0/2.0/0.0"
+pub fn simple_light(options: &SceneOptions) -> Scene {
+    let mut objects = Vec::<Box<dyn Hittable>>::new();
+
+    let checker = Box::new(CheckerTexture::from_color(
+        0.32,
+        Color::new([0.2, 0.3, 0.1]),
+        Color::new([0.9, 0.9, 0.9]),
+    ));
+
+    objects.push(Box::new(Sphere::new(
+        Vector::new([0.0, -1000.0, 0.0]),
+        1000.0,
+        Some(Box::new(Lambertian::with_texture(checker))),
+    )));
+    objects.push(Box::new(Sphere::new(
+        Vector::new([0.0, 2.0, 0.0]),
+        2.0,
+        Some(Box::new(Lambertian::new(Color::new([0.4, 0.2, 0.1])))),
+    )));
+
+    // lights
+    let sphere_light = |material| Sphere::new(Vector::new([0.0, 7.0, 0.0]), 2.0, material);
+    let quad_light = |material| {
+        Quad::new(
+            Vector::new([3.0, 1.0, -2.0]),
+            Vector::new([2.0, 0.0, 0.0]),
+            Vector::new([0.0, 2.0, 0.0]),
+            material,
+        )
+    };
+    objects.push(Box::new(sphere_light(Some(Box::new(DiffuseLight::new(
+        Color::new_one(4.0),
+    ))))));
+    objects.push(Box::new(quad_light(Some(Box::new(DiffuseLight::new(
+        Color::new_one(4.0),
+    ))))));
+
+    let mut world = HittableList::new();
+    world.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+
+    let mut lights = HittableList::new();
+    lights.add(Box::new(sphere_light(None)));
+    lights.add(Box::new(quad_light(None)));
+
+    Scene {
+        world,
+        background: Background::None,
+        lights,
+    }
+}
+
+// the red, green and white walls of the cornell box (without the light)
+fn cornell_box_walls() -> Vec<Box<dyn Hittable>> {
+    type M = Box<dyn Material>;
+    let red = || Box::new(Lambertian::new(Color::new([0.65, 0.05, 0.05]))) as M;
+    let white = || Box::new(Lambertian::new(Color::new_one(0.73))) as M;
+    let green = || Box::new(Lambertian::new(Color::new([0.12, 0.45, 0.15]))) as M;
+
+    vec![
+        Box::new(Quad::new(
+            Vector::new([555.0, 0.0, 0.0]),
+            Vector::new([0.0, 555.0, 0.0]),
+            Vector::new([0.0, 0.0, 555.0]),
+            Some(green()),
+        )),
+        Box::new(Quad::new(
+            Vector::new([0.0, 0.0, 0.0]),
+            Vector::new([0.0, 555.0, 0.0]),
+            Vector::new([0.0, 0.0, 555.0]),
+            Some(red()),
+        )),
+        Box::new(Quad::new(
+            Vector::new([0.0, 0.0, 0.0]),
+            Vector::new([555.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, 555.0]),
+            Some(white()),
+        )),
+        Box::new(Quad::new(
+            Vector::new([555.0, 555.0, 555.0]),
+            Vector::new([-555.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, -555.0]),
+            Some(white()),
+        )),
+        Box::new(Quad::new(
+            Vector::new([0.0, 0.0, 555.0]),
+            Vector::new([555.0, 0.0, 0.0]),
+            Vector::new([0.0, 555.0, 0.0]),
+            Some(white()),
+        )),
+    ]
+}
+
+// the two rotated boxes inside the cornell box
+fn cornell_box_boxes() -> [Instance; 2] {
+    let white = || Box::new(Lambertian::new(Color::new_one(0.73))) as Box<dyn Material>;
+
+    let box1 = make_box(
+        Vector::new([0.0, 0.0, 0.0]),
+        Vector::new([165.0, 330.0, 165.0]),
+        white,
+    );
+    let transform1 = Transform::rotation_y(15.0).translate(Vector::new([265.0, 0.0, 295.0]));
+
+    let box2 = make_box(
+        Vector::new([0.0, 0.0, 0.0]),
+        Vector::new([165.0, 165.0, 165.0]),
+        white,
+    );
+    let transform2 = Transform::rotation_y(-18.0).translate(Vector::new([130.0, 0.0, 65.0]));
+
+    [
+        Instance::new(Box::new(box1), transform1),
+        Instance::new(Box::new(box2), transform2),
+    ]
+}
+
+// best viewed from "278.0/278.0/-800.0" looking at "278.0/278.0/0.0" with vfov of 40
+pub fn cornell_box(options: &SceneOptions) -> Scene {
+    let mut objects = cornell_box_walls();
+
+    let light = |material| {
+        Quad::new(
+            Vector::new([343.0, 554.0, 332.0]),
+            Vector::new([-130.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, -105.0]),
+            material,
+        )
+    };
+    objects.push(Box::new(light(Some(Box::new(DiffuseLight::new(
+        Color::new_one(15.0),
+    ))))));
+
+    for instance in cornell_box_boxes() {
+        objects.push(Box::new(instance));
+    }
+
+    let mut world = HittableList::new();
+    world.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+
+    let mut lights = HittableList::new();
+    lights.add(Box::new(light(None)));
+
+    Scene {
+        world,
+        background: Background::None,
+        lights,
+    }
+}
+
+// best viewed from "278.0/278.0/-800.0" looking at "278.0/278.0/0.0" with vfov of 40
+pub fn cornell_smoke(options: &SceneOptions) -> Scene {
+    let mut objects = cornell_box_walls();
+
+    let light = |material| {
+        Quad::new(
+            Vector::new([113.0, 554.0, 127.0]),
+            Vector::new([330.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, 305.0]),
+            material,
+        )
+    };
+    objects.push(Box::new(light(Some(Box::new(DiffuseLight::new(
+        Color::new_one(7.0),
+    ))))));
+
+    let [box1, box2] = cornell_box_boxes();
+    objects.push(Box::new(ConstantMedium::from_color(
+        Box::new(box1),
+        0.01,
+        Color::new_one(0.0),
+    )));
+    objects.push(Box::new(ConstantMedium::from_color(
+        Box::new(box2),
+        0.01,
+        Color::new_one(1.0),
+    )));
+
+    let mut world = HittableList::new();
+    world.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+
+    let mut lights = HittableList::new();
+    lights.add(Box::new(light(None)));
+
+    Scene {
+        world,
+        background: Background::None,
+        lights,
+    }
+}
+
+// best viewed from "478.0/278.0/-600.0" looking at "278.0/278.0/0.0" with vfov of 40
+pub fn final_scene(options: &SceneOptions) -> Scene {
+    util::seed_rng(options.seed);
+    let mut objects = Vec::<Box<dyn Hittable>>::new();
+
+    // ground made of boxes with random heights
+    let mut ground = Vec::<Box<dyn Hittable>>::new();
+    let boxes_per_side = 20;
+    for i in 0..boxes_per_side {
+        for j in 0..boxes_per_side {
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let z0 = -1000.0 + j as f64 * w;
+            let y1 = util::get_random(1.0, 101.0);
+
+            ground.push(Box::new(make_box(
+                Vector::new([x0, 0.0, z0]),
+                Vector::new([x0 + w, y1, z0 + w]),
+                || Box::new(Lambertian::new(Color::new([0.48, 0.83, 0.53]))),
+            )));
+        }
+    }
+    objects.push(Box::new(BvhNode::with_params(ground, &options.bvh)));
+
+    // light
+    let light = |material| {
+        Quad::new(
+            Vector::new([123.0, 554.0, 147.0]),
+            Vector::new([300.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, 265.0]),
+            material,
+        )
+    };
+    objects.push(Box::new(light(Some(Box::new(DiffuseLight::new(
+        Color::new_one(7.0),
+    ))))));
+
+    let center1 = Vector::new([400.0, 400.0, 200.0]);
+    let center2 = center1 + Vector::new([30.0, 0.0, 0.0]);
+    objects.push(Box::new(Sphere::new_moving(
+        center1,
+        center2,
+        50.0,
+        Some(Box::new(Lambertian::new(Color::new([0.7, 0.3, 0.1])))),
+    )));
+
+    objects.push(Box::new(Sphere::new(
+        Vector::new([260.0, 150.0, 45.0]),
+        50.0,
+        Some(Box::new(Dielectric::new(1.5))),
+    )));
+    objects.push(Box::new(Sphere::new(
+        Vector::new([0.0, 150.0, 145.0]),
+        50.0,
+        Some(Box::new(Metal::new(Color::new([0.8, 0.8, 0.9]), 1.0))),
+    )));
+
+    // glass sphere filled with blue smoke
+    let boundary = || {
+        Sphere::new(
+            Vector::new([360.0, 150.0, 145.0]),
+            70.0,
+            Some(Box::new(Dielectric::new(1.5))),
+        )
+    };
+    objects.push(Box::new(boundary()));
+    objects.push(Box::new(ConstantMedium::from_color(
+        Box::new(boundary()),
+        0.2,
+        Color::new([0.2, 0.4, 0.9]),
+    )));
+
+    // thin mist over the whole scene
+    objects.push(Box::new(ConstantMedium::from_color(
+        Box::new(Sphere::new(Vector::new([0.0, 0.0, 0.0]), 5000.0, None)),
+        0.0001,
+        Color::new_one(1.0),
+    )));
+
+    objects.push(Box::new(Sphere::new(
+        Vector::new([400.0, 200.0, 400.0]),
+        100.0,
+        Some(Box::new(Lambertian::new(Color::new([0.2, 0.4, 0.8])))),
+    )));
+    let marble = Box::new(MarbleTexture::new(0.2, 7, 0));
+    objects.push(Box::new(Sphere::new(
+        Vector::new([220.0, 280.0, 300.0]),
+        80.0,
+        Some(Box::new(Lambertian::with_texture(marble))),
+    )));
+
+    // cluster of small spheres
+    let cluster = (0..1000)
+        .map(|_| {
+            Box::new(Sphere::new(
+                vec::random_vector(0.0, 165.0),
+                10.0,
+                Some(Box::new(Lambertian::new(Color::new_one(0.73)))),
+            )) as Box<dyn Hittable>
+        })
+        .collect::<Vec<_>>();
+    let transform = Transform::rotation_y(15.0).translate(Vector::new([-100.0, 270.0, 395.0]));
+    objects.push(Box::new(Instance::new(
+        Box::new(BvhNode::with_params(cluster, &options.bvh)),
+        transform,
+    )));
+
+    let mut world = HittableList::new();
+    world.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+
+    let mut lights = HittableList::new();
+    lights.add(Box::new(light(None)));
+
+    Scene {
+        world,
+        background: Background::None,
+        lights,
+    }
+}
+
+// expects the texture 'earthmap.jpg' in the working directory, best viewed from "0.0/0.0/12.0"
+pub fn earth(_options: &SceneOptions) -> Scene {
+    let earth_texture = Box::new(ImageTexture::load(Path::new("earthmap.jpg")));
+
+    let mut world = HittableList::new();
+    world.add(Box::new(Sphere::new(
+        Vector::new([0.0, 0.0, 0.0]),
+        2.0,
+        Some(Box::new(Lambertian::with_texture(earth_texture))),
+    )));
+
+    Scene::with_sky(world)
+}
+
+pub fn perlin_spheres(options: &SceneOptions) -> Scene {
+    let mut objects = Vec::<Box<dyn Hittable>>::new();
+
+    let ground = Box::new(NoiseTexture::new(4.0, 0).with_ramp(ColorRamp::two_colors(
+        Color::new([0.1, 0.2, 0.1]),
+        Color::new([0.6, 0.8, 0.5]),
+    )));
+    objects.push(Box::new(Sphere::new(
+        Vector::new([0.0, -1000.0, 0.0]),
+        1000.0,
+        Some(Box::new(Lambertian::with_texture(ground))),
+    )));
+
+    let marble = Box::new(MarbleTexture::new(4.0, 7, 0));
+    objects.push(Box::new(Sphere::new(
+        Vector::new([0.0, 2.0, 0.0]),
+        2.0,
+        Some(Box::new(Lambertian::with_texture(marble))),
+    )));
+
+    let mut scene = HittableList::new();
+    scene.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
+
+    Scene::with_sky(scene)
+}
+
+// ring in the xz plane centered at the origin
+fn torus_mesh(
+    major_radius: f64,
+    minor_radius: f64,
+    segments: usize,
+    sides: usize,
+    material: Box<dyn Material>,
+) -> MeshBuffers {
+    let mut positions = Vec::with_capacity(segments * sides);
+    let mut normals = Vec::with_capacity(segments * sides);
+    for i in 0..segments {
+        let (sin_u, cos_u) = (i as f64 / segments as f64 * std::f64::consts::TAU).sin_cos();
+        let ring_center = Vector::new([cos_u, 0.0, sin_u]) * major_radius;
+        for j in 0..sides {
+            let (sin_v, cos_v) = (j as f64 / sides as f64 * std::f64::consts::TAU).sin_cos();
+            let normal = Vector::new([cos_u * cos_v, sin_v, sin_u * cos_v]);
+            positions.push(ring_center + normal * minor_radius);
+            normals.push(normal);
+        }
+    }
+
+    let index = |i: usize, j: usize| (i % segments) * sides + (j % sides);
+    let indices = (0..segments)
+        .flat_map(|i| {
+            (0..sides).flat_map(move |j| {
+                let quad = [
+                    index(i, j),
+                    index(i + 1, j),
+                    index(i + 1, j + 1),
+                    index(i, j + 1),
+                ];
+                [[quad[0], quad[2], quad[1]], [quad[0], quad[3], quad[2]]]
+            })
+        })
+        .collect();
+
+    MeshBuffers {
+        positions,
+        normals: Some(normals),
+        uvs: None,
+        indices,
+        material: Some(material),
+    }
+}
+
+// 3600 tori sharing two bottom-level bvhs, best viewed from "0.0/30.0/60.0" looking at
+// "0.0/0.0/0.0"
+pub fn instancing(options: &SceneOptions) -> Scene {
+    util::seed_rng(options.seed);
+    let mut objects = Vec::<Box<dyn Hittable>>::new();
+
+    objects.push(Box::new(Sphere::new(
+        Vector::new([0.0, -1000.0, 0.0]),
+        1000.0,
+        Some(Box::new(Lambertian::new(Color::new([0.5, 0.5, 0.5])))),
+    )));
+
+    // every unique piece of geometry is built only once, the mesh already is a bvh of its own
+    let blas = |material: Box<dyn Material>| -> Arc<dyn Hittable> {
+        Arc::new(TriangleMesh::new(torus_mesh(1.0, 0.3, 48, 16, material)))
+    };
+    let shared = [
+        blas(Box::new(Metal::new(Color::new([0.8, 0.6, 0.2]), 0.2))),
+        blas(Box::new(Lambertian::new(Color::new([0.2, 0.3, 0.7])))),
+    ];
+
+    let mut instances = Vec::<Box<dyn Hittable>>::new();
+    for a in -30i32..30 {
+        for b in -30..30 {
+            let scale = util::get_random(0.3, 0.6);
+            let transform = Transform::scaling(Vector::new([scale, scale, scale]))
+                .rotate_x(util::get_random(0.0, 360.0))
+                .rotate_y(util::get_random(0.0, 360.0))
+                .translate(Vector::new([
+                    a as f64 * 1.5 + util::get_random(0.0, 0.5),
+                    scale * 1.3,
+                    b as f64 * 1.5 + util::get_random(0.0, 0.5),
+                ]));
+            let object = shared[(a + b).rem_euclid(2) as usize].clone();
+            instances.push(Box::new(Instance::shared(object, transform)));
+        }
+    }
+    objects.push(Box::new(BvhNode::with_params(instances, &options.bvh)));
+
+    let mut scene = HittableList::new();
+    scene.add(Box::new(BvhNode::with_params(objects, &options.bvh)));
 
-    scene
+    Scene::with_sky(scene)
 }
@@ -1,10 +1,16 @@
+use std::path::Path;
+
+use image::error::{ImageError, ParameterError, ParameterErrorKind};
+
 use crate::color::Color;
+use crate::noise::Perlin;
+use crate::util;
 use crate::vec::Vector;
 
 type Vec2 = Vector<f64, 2>;
 type Vec3 = Vector<f64, 3>;
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn value(&self, uv: Vec2, point: Vec3) -> Color;
 }
 
@@ -64,3 +70,364 @@ impl Texture for CheckerTexture {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+// how texture coordinates outside of [0, 1] are handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+}
+
+// A texture sampled from an image, texels are stored in linear space
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+    filter: Filter,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    // a missing or invalid image is replaced by a magenta texture so the problem is visible
+    pub fn load(path: &Path) -> Self {
+        Self::from_file(path).unwrap_or_else(|e| {
+            eprintln!(
+                "Failed to load image texture '{}': {}. Using fallback texture",
+                path.display(),
+                e
+            );
+            Self::from_texels(1, 1, vec![Color::new([1.0, 0.0, 1.0])])
+        })
+    }
+
+    // an empty image is an error, there would be no texel to sample
+    pub fn from_file(path: &Path) -> Result<Self, ImageError> {
+        let image = image::open(path)?.into_rgb32f();
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+
+        let texels = image
+            .pixels()
+            .map(|p| Color::new(p.0.map(|v| util::srgb_to_linear(v as f64))))
+            .collect();
+
+        Ok(Self::from_texels(width as usize, height as usize, texels))
+    }
+
+    // texels are in row-major order starting from the top-left corner
+    pub fn from_texels(width: usize, height: usize, texels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "Texture needs at least one texel");
+        assert_eq!(texels.len(), width * height, "Texel count mismatch");
+        Self {
+            width,
+            height,
+            texels,
+            filter: Filter::Bilinear,
+            wrap: WrapMode::Repeat,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let wrap = |value: i64, size: usize| match self.wrap {
+            WrapMode::Repeat => value.rem_euclid(size as i64) as usize,
+            WrapMode::Clamp => value.clamp(0, size as i64 - 1) as usize,
+        };
+        self.texels[wrap(y, self.height) * self.width + wrap(x, self.width)].clone()
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, uv: Vec2, _point: Vec3) -> Color {
+        // v goes up while image rows go down
+        let x = uv[0] * self.width as f64;
+        let y = (1.0 - uv[1]) * self.height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // texel centers are at half-integer coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = self.texel(x0, y0) * (1.0 - tx) + self.texel(x0 + 1, y0) * tx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            }
+        }
+    }
+}
+
+// Maps a value in [0, 1] to a color by interpolating between color stops
+#[derive(Clone, Debug)]
+pub struct ColorRamp {
+    stops: Vec<(f64, Color)>,
+}
+
+impl ColorRamp {
+    // stops are sorted by their position, the ones at NaN are dropped, at least one stop is
+    // required
+    pub fn new(mut stops: Vec<(f64, Color)>) -> Self {
+        stops.retain(|s| !s.0.is_nan());
+        assert!(!stops.is_empty(), "Color ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { stops }
+    }
+
+    pub fn two_colors(from: Color, to: Color) -> Self {
+        Self::new(vec![(0.0, from), (1.0, to)])
+    }
+
+    pub fn sample(&self, t: f64) -> Color {
+        let first = &self.stops[0];
+        let last = &self.stops[self.stops.len() - 1];
+
+        // NaN maps to the first stop as well
+        if t.is_nan() || t <= first.0 {
+            return first.1.clone();
+        }
+        if t >= last.0 {
+            return last.1.clone();
+        }
+
+        let next = self.stops.iter().position(|s| s.0 > t).unwrap();
+        let (t0, c0) = &self.stops[next - 1];
+        let (t1, c1) = &self.stops[next];
+        let a = (t - t0) / (t1 - t0);
+        c0.clone() * (1.0 - a) + c1.clone() * a
+    }
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self {
+        Self::two_colors(Color::new_one(0.0), Color::new_one(1.0))
+    }
+}
+
+// Perlin noise remapped to [0, 1]
+pub struct NoiseTexture {
+    perlin: Perlin,
+    scale: f64,
+    ramp: ColorRamp,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            ramp: ColorRamp::default(),
+        }
+    }
+
+    pub fn with_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _uv: Vec2, point: Vec3) -> Color {
+        let noise = self.perlin.noise(point * self.scale);
+        self.ramp.sample(0.5 * (1.0 + noise))
+    }
+}
+
+// Fractal sum of perlin noise
+pub struct TurbulenceTexture {
+    perlin: Perlin,
+    scale: f64,
+    octaves: u32,
+    ramp: ColorRamp,
+}
+
+impl TurbulenceTexture {
+    pub fn new(scale: f64, octaves: u32, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            octaves,
+            ramp: ColorRamp::default(),
+        }
+    }
+
+    pub fn with_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+}
+
+impl Texture for TurbulenceTexture {
+    fn value(&self, _uv: Vec2, point: Vec3) -> Color {
+        let turbulence = self.perlin.turbulence(point * self.scale, self.octaves);
+        self.ramp.sample(turbulence)
+    }
+}
+
+// Marble-like veins: a sine wave along z with its phase disturbed by turbulence
+pub struct MarbleTexture {
+    perlin: Perlin,
+    scale: f64,
+    octaves: u32,
+    ramp: ColorRamp,
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64, octaves: u32, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            octaves,
+            ramp: ColorRamp::default(),
+        }
+    }
+
+    pub fn with_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _uv: Vec2, point: Vec3) -> Color {
+        let turbulence = self.perlin.turbulence(point, self.octaves);
+        let phase = self.scale * point[2] + 10.0 * turbulence;
+        self.ramp.sample(0.5 * (1.0 + phase.sin()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture() -> ImageTexture {
+        // 2x2: black, white / red, green
+        ImageTexture::from_texels(
+            2,
+            2,
+            vec![
+                Color::new([0.0, 0.0, 0.0]),
+                Color::new([1.0, 1.0, 1.0]),
+                Color::new([1.0, 0.0, 0.0]),
+                Color::new([0.0, 1.0, 0.0]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_nearest() {
+        let texture = texture().with_filter(Filter::Nearest);
+        let point = Vec3::default();
+
+        assert_eq!(
+            texture.value([0.25, 0.75].into(), point),
+            Color::new_one(0.0)
+        );
+        assert_eq!(
+            texture.value([0.75, 0.75].into(), point),
+            Color::new_one(1.0)
+        );
+        assert_eq!(
+            texture.value([0.25, 0.25].into(), point),
+            Color::new([1.0, 0.0, 0.0])
+        );
+        assert_eq!(
+            texture.value([1.25, 1.75].into(), point),
+            Color::new_one(0.0)
+        );
+
+        let clamped = texture.with_wrap(WrapMode::Clamp);
+        assert_eq!(
+            clamped.value([1.25, 1.75].into(), point),
+            Color::new_one(1.0)
+        );
+    }
+
+    #[test]
+    fn test_bilinear() {
+        let texture = texture().with_wrap(WrapMode::Clamp);
+        let point = Vec3::default();
+
+        assert_eq!(
+            texture.value([0.25, 0.75].into(), point),
+            Color::new_one(0.0)
+        );
+        assert_eq!(
+            texture.value([0.5, 0.75].into(), point),
+            Color::new_one(0.5)
+        );
+        assert_eq!(
+            texture.value([0.5, 0.5].into(), point),
+            Color::new([0.5, 0.5, 0.25])
+        );
+    }
+
+    #[test]
+    fn test_color_ramp() {
+        let ramp = ColorRamp::new(vec![
+            (1.0, Color::new([0.0, 0.0, 1.0])),
+            (0.0, Color::new([1.0, 0.0, 0.0])),
+            (0.5, Color::new([0.0, 1.0, 0.0])),
+        ]);
+
+        assert_eq!(ramp.sample(-1.0), Color::new([1.0, 0.0, 0.0]));
+        assert_eq!(ramp.sample(0.25), Color::new([0.5, 0.5, 0.0]));
+        assert_eq!(ramp.sample(0.5), Color::new([0.0, 1.0, 0.0]));
+        assert_eq!(ramp.sample(0.75), Color::new([0.0, 0.5, 0.5]));
+        assert_eq!(ramp.sample(2.0), Color::new([0.0, 0.0, 1.0]));
+        assert_eq!(ramp.sample(f64::NAN), Color::new([1.0, 0.0, 0.0]));
+
+        let ramp = ColorRamp::new(vec![
+            (f64::NAN, Color::new([0.0, 0.0, 1.0])),
+            (0.5, Color::new([0.0, 1.0, 0.0])),
+        ]);
+        assert_eq!(ramp.sample(1.0), Color::new([0.0, 1.0, 0.0]));
+    }
+
+    #[test]
+    fn test_fallback() {
+        let texture = ImageTexture::load(Path::new("this/file/does/not/exist.png"));
+        assert_eq!(
+            texture.value([0.3, 0.6].into(), Vec3::default()),
+            Color::new([1.0, 0.0, 1.0])
+        );
+
+        let path = std::env::temp_dir().join(format!("empty-texture-{}.ppm", std::process::id()));
+        std::fs::write(&path, "P6\n0 0\n255\n").unwrap();
+        let texture = ImageTexture::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            texture.value([0.3, 0.6].into(), Vec3::default()),
+            Color::new([1.0, 0.0, 1.0])
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::color::Color;
+use crate::util;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    // values above 1 are cut off
+    Clamp,
+    // L / (1 + L) on the luminance
+    Reinhard,
+    // Reinhard that maps the luminance `white_point` to 1
+    ExtendedReinhard,
+    // filmic curve from Uncharted 2 (Hable)
+    Hable,
+    // fit of the ACES filmic curve (Narkowicz)
+    Aces,
+}
+
+impl FromStr for Operator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clamp" => Ok(Operator::Clamp),
+            "reinhard" => Ok(Operator::Reinhard),
+            "reinhard-extended" => Ok(Operator::ExtendedReinhard),
+            "hable" => Ok(Operator::Hable),
+            "aces" => Ok(Operator::Aces),
+            _ => Err(format!("Unknown tone mapping operator '{}'", s)),
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Clamp => write!(f, "clamp"),
+            Operator::Reinhard => write!(f, "reinhard"),
+            Operator::ExtendedReinhard => write!(f, "reinhard-extended"),
+            Operator::Hable => write!(f, "hable"),
+            Operator::Aces => write!(f, "aces"),
+        }
+    }
+}
+
+// encoding from linear to display values
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transfer {
+    // piecewise sRGB curve
+    Srgb,
+    // square root, the approximation used by the book
+    Gamma2,
+    // no encoding, for data that isn't a color (e.g. normals or depth)
+    Linear,
+}
+
+impl FromStr for Transfer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "srgb" => Ok(Transfer::Srgb),
+            "gamma2" => Ok(Transfer::Gamma2),
+            "linear" => Ok(Transfer::Linear),
+            _ => Err(format!("Unknown transfer function '{}'", s)),
+        }
+    }
+}
+
+// Post processing that turns the rendered radiance into display values in [0, 1]: exposure, then
+// tone mapping, then the transfer function
+#[derive(Clone, Debug)]
+pub struct ToneMapping {
+    // in stops, each stop doubles the brightness
+    pub exposure: f64,
+    pub operator: Operator,
+    // smallest luminance that becomes white, only used by the extended Reinhard
+    pub white_point: f64,
+    pub transfer: Transfer,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            operator: Operator::Clamp,
+            white_point: 4.0,
+            transfer: Transfer::Srgb,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn apply(&self, color: &Color) -> Color {
+        let color = color.clone() * 2f64.powf(self.exposure);
+        let mapped = self.map(color).transform(|v| match v.is_nan() {
+            true => 0.0,
+            false => v.clamp(0.0, 1.0),
+        });
+
+        match self.transfer {
+            Transfer::Srgb => mapped.transform(util::linear_to_srgb),
+            Transfer::Gamma2 => mapped.transform(util::linear_to_gamma),
+            Transfer::Linear => mapped,
+        }
+    }
+
+    fn map(&self, color: Color) -> Color {
+        match self.operator {
+            Operator::Clamp => color,
+            Operator::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            Operator::ExtendedReinhard => {
+                let white_squared = self.white_point * self.white_point;
+                scale_luminance(color, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            }
+            Operator::Hable => {
+                // see: "Filmic Tonemapping Operators" (Hable, 2010)
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const LINEAR_WHITE: f64 = 11.2;
+                color.transform(|v| hable_curve(v * EXPOSURE_BIAS) / hable_curve(LINEAR_WHITE))
+            }
+            Operator::Aces => {
+                // see: "ACES Filmic Tone Mapping Curve" (Narkowicz, 2015)
+                color.transform(|v| (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14))
+            }
+        }
+    }
+}
+
+fn hable_curve(x: f64) -> f64 {
+    const A: f64 = 0.15; // shoulder strength
+    const B: f64 = 0.50; // linear strength
+    const C: f64 = 0.10; // linear angle
+    const D: f64 = 0.20; // toe strength
+    const E: f64 = 0.02; // toe numerator
+    const F: f64 = 0.30; // toe denominator
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+// applies `f` to the luminance and scales the color to match, so the hue is kept
+fn scale_luminance(color: Color, f: impl Fn(f64) -> f64) -> Color {
+    let luminance = color.luminance();
+    if luminance <= 0.0 {
+        return color;
+    }
+    color * (f(luminance) / luminance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_operator(operator: Operator) -> ToneMapping {
+        ToneMapping {
+            operator,
+            transfer: Transfer::Gamma2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_operators() {
+        for operator in [
+            Operator::Clamp,
+            Operator::Reinhard,
+            Operator::ExtendedReinhard,
+            Operator::Hable,
+            Operator::Aces,
+        ] {
+            let tone_mapping = with_operator(operator);
+            let mut previous = -1.0;
+            for i in 0..200 {
+                // monotonic and within [0, 1]
+                let value = *tone_mapping.apply(&Color::new_one(i as f64 * 0.1)).r();
+                assert!((0.0..=1.0).contains(&value));
+                assert!(value >= previous, "{} is not monotonic", operator);
+                previous = value;
+            }
+            assert_eq!(*tone_mapping.apply(&Color::new_one(0.0)).g(), 0.0);
+            assert_eq!(operator.to_string().parse(), Ok(operator));
+        }
+
+        // the white point maps to 1
+        let white = with_operator(Operator::ExtendedReinhard).apply(&Color::new_one(4.0));
+        assert!((white.r() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_exposure() {
+        let tone_mapping = ToneMapping {
+            exposure: 1.0,
+            transfer: Transfer::Gamma2,
+            ..Default::default()
+        };
+        let color = tone_mapping.apply(&Color::new([0.125, 0.5, 2.0]));
+        assert_eq!(*color.r(), 0.5);
+        assert_eq!(*color.g(), 1.0);
+        assert_eq!(*color.b(), 1.0);
+    }
+
+    #[test]
+    fn test_srgb() {
+        let srgb = ToneMapping::default();
+        assert_eq!(*srgb.apply(&Color::new_one(0.0)).r(), 0.0);
+        assert!((srgb.apply(&Color::new_one(0.002)).r() - 0.002 * 12.92).abs() < 1e-12);
+        assert!((srgb.apply(&Color::new_one(0.5)).r() - 0.7354).abs() < 1e-4);
+        assert!((srgb.apply(&Color::new_one(1.0)).r() - 1.0).abs() < 1e-12);
+        assert!((util::srgb_to_linear(util::linear_to_srgb(0.3)) - 0.3).abs() < 1e-12);
+    }
+}
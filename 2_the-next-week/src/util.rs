@@ -1,17 +1,40 @@
+use std::cell::RefCell;
+
 use num::Num;
 use rand::distributions::uniform::SampleUniform;
 use rand::distributions::{Distribution, Uniform};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+thread_local! {
+    // every random value in the renderer comes from here, see `seed_rng`
+    static RNG: RefCell<ChaCha8Rng> = RefCell::new(ChaCha8Rng::from_entropy());
+}
+
+// restarts the random sequence of the current thread, the same seed always gives the same values
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = ChaCha8Rng::seed_from_u64(seed));
+}
+
+// combines a seed with stream indices (e.g. pixel and sample index) into a new seed, so every
+// stream gets its own independent sequence (splitmix64 finalizer)
+pub fn derive_seed(seed: u64, streams: &[u64]) -> u64 {
+    streams.iter().fold(seed, |acc, stream| {
+        let mut z =
+            (acc ^ stream.wrapping_mul(0x9e3779b97f4a7c15)).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    })
+}
 
 pub fn get_random_canonical() -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.gen()
+    RNG.with(|rng| rng.borrow_mut().gen())
 }
 
 pub fn get_random<T: Num + SampleUniform>(from: T, to: T) -> T {
-    let mut rng = rand::thread_rng();
     let dist = Uniform::<T>::new(from, to);
-    dist.sample(&mut rng)
+    RNG.with(|rng| dist.sample(&mut *rng.borrow_mut()))
 }
 
 pub fn random() {}
@@ -19,3 +42,21 @@ pub fn random() {}
 pub fn linear_to_gamma(linear: f64) -> f64 {
     linear.sqrt()
 }
+
+// encode a linear value with the piecewise sRGB transfer function
+pub fn linear_to_srgb(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// decode an sRGB encoded value (e.g. from an image file) into linear space
+pub fn srgb_to_linear(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
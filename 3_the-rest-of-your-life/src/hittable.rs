@@ -0,0 +1,385 @@
+use std::array;
+use std::f64::consts::PI;
+
+use crate::aabb::AABB;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::util;
+use crate::vec::{self, Vector};
+
+type Vec3 = Vector<f64, 3>;
+type Vec2 = Vector<f64, 2>;
+type Ray3 = Ray<f64, 3>;
+type AABB3 = AABB<f64, 3>;
+
+#[derive(Clone)]
+pub struct HitRecord {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub tex: Vec2,
+    pub t_value: f64,
+    pub front_face: bool,
+}
+
+impl HitRecord {
+    pub fn new(ray: Ray3, out_normal: Vec3, point: Vec3, tex_hit: Vec2, t_value: f64) -> HitRecord {
+        let front_face = ray.direction.dot(out_normal) < 0.0;
+        let normal = if front_face { out_normal } else { -out_normal };
+
+        Self {
+            point,
+            normal,
+            tex: tex_hit,
+            t_value,
+            front_face,
+        }
+    }
+}
+
+pub struct HitResult<'a> {
+    pub record: HitRecord,
+    pub material: Option<&'a dyn Material>,
+}
+
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>>;
+    fn get_material(&self) -> Option<&dyn Material> {
+        None
+    }
+    fn bounding_box(&self) -> &AABB3;
+
+    // Density (over solid angle) of the directions from `origin` returned by `random_direction`.
+    // Zero for the directions missing the object and for the objects that can't be sampled.
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f64 {
+        0.0
+    }
+    // random direction from `origin` towards a point of the object
+    fn random_direction(&self, _origin: Vec3) -> Vec3 {
+        Vec3::new([1.0, 0.0, 0.0])
+    }
+}
+
+// the ray starting at `origin` going to `direction`, used to find where a sampled direction hits
+fn probe_ray(origin: Vec3, direction: Vec3) -> Ray3 {
+    Ray3 {
+        origin,
+        direction,
+        time: 0.0,
+    }
+}
+
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f64,
+    pub material: Option<Box<dyn Material>>,
+    bbox: AABB3,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3, radius: f64, material: Option<Box<dyn Material>>) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+            bbox: AABB3::new(array::from_fn(|i| {
+                Interval::new(center[i] - radius, center[i] + radius)
+            })),
+        }
+    }
+
+    // maps a point on the unit sphere to (longitude, latitude) normalized to [0, 1], u starts at
+    // -x going around the y axis and v goes from -y to +y
+    pub fn sphere_uv(point: Vec3) -> Vec2 {
+        let theta = (-point[1]).clamp(-1.0, 1.0).acos();
+        let phi = (-point[2]).atan2(point[0]) + PI;
+
+        Vec2::new([phi / (2.0 * PI), theta / PI])
+    }
+
+    // cosine of the half angle of the cone around the sphere seen from `origin`, none if `origin`
+    // is inside the sphere (every direction hits it then)
+    fn cos_theta_max(&self, origin: Vec3) -> Option<f64> {
+        let distance_squared = (self.center - origin).length_squared();
+        match distance_squared > self.radius * self.radius {
+            true => Some((1.0 - self.radius * self.radius / distance_squared).sqrt()),
+            false => None,
+        }
+    }
+}
+
+impl Hittable for Sphere {
+    fn get_material(&self) -> Option<&dyn Material> {
+        self.material.as_deref()
+    }
+
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        // basically quadratic formula
+        let oc = ray.origin - self.center;
+        let a = ray.direction.length_squared();
+        let b_half = oc.dot(ray.direction);
+        let c = oc.length_squared() - self.radius * self.radius;
+
+        let d = b_half * b_half - a * c;
+        if d < 0.0 {
+            return None;
+        }
+
+        let d_sqrt = d.sqrt();
+        let root1 = (-b_half - d_sqrt) / a;
+        let root2 = (-b_half + d_sqrt) / a;
+        let root = match (t_range.surrounds(root1), t_range.surrounds(root2)) {
+            (false, false) => return None,
+            (false, true) => root2,
+            (true, _) => root1,
+        };
+
+        let point = ray.at(root);
+        let out_normal = (point - self.center) / self.radius;
+        let tex = Self::sphere_uv(out_normal);
+
+        Some(HitResult {
+            record: HitRecord::new(ray, out_normal, point, tex, root),
+            material: self.get_material(),
+        })
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        &self.bbox
+    }
+
+    // uniform over the cone of directions seen by the sphere
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let ray = probe_ray(origin, direction);
+        if self.hit(ray, Interval::new(0.001, f64::INFINITY)).is_none() {
+            return 0.0;
+        }
+
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => 1.0 / (2.0 * PI * (1.0 - cos_theta_max)),
+            None => 1.0 / (4.0 * PI),
+        }
+    }
+
+    fn random_direction(&self, origin: Vec3) -> Vec3 {
+        let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+            return vec::random_unit_vector();
+        };
+
+        let z = 1.0 + util::get_random_canonical() * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * util::get_random_canonical();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+
+        let uvw = Onb::new(self.center - origin);
+        uvw.transform(Vec3::new([r * phi.cos(), r * phi.sin(), z]))
+    }
+}
+
+// A parallelogram spanned by the edges `u` and `v` starting from the corner `q`
+pub struct Quad {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Option<Box<dyn Material>>,
+    bbox: AABB3,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: Option<Box<dyn Material>>) -> Self {
+        // a quad is flat, so the box is padded
+        let bbox_diagonal1 = AABB3::from_points(q, q + u + v);
+        let bbox_diagonal2 = AABB3::from_points(q + u, q + v);
+        let bbox = bbox_diagonal1
+            .combine_new(&bbox_diagonal2)
+            .pad_to_minimums(0.0001);
+
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+
+        Self {
+            q,
+            u,
+            v,
+            material,
+            bbox,
+            normal,
+            d: normal.dot(q),
+            w: n / n.dot(n),
+            area: n.length(),
+        }
+    }
+
+    // returns the planar coordinates of the point if it lies inside the quad
+    fn planar_coordinates(&self, point: Vec3) -> Option<Vec2> {
+        let planar_hit = point - self.q;
+        let alpha = self.w.dot(planar_hit.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hit));
+
+        let unit = Interval::new(0.0, 1.0);
+        match unit.contains(alpha) && unit.contains(beta) {
+            true => Some(Vec2::new([alpha, beta])),
+            false => None,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn get_material(&self) -> Option<&dyn Material> {
+        self.material.as_deref()
+    }
+
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        let denom = self.normal.dot(ray.direction);
+
+        // ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(ray.origin)) / denom;
+        if !t_range.contains(t) {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let tex = self.planar_coordinates(point)?;
+
+        Some(HitResult {
+            record: HitRecord::new(ray, self.normal, point, tex, t),
+            material: self.get_material(),
+        })
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        &self.bbox
+    }
+
+    // uniform over the area, converted to solid angle
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let ray = probe_ray(origin, direction);
+        let Some(hit) = self.hit(ray, Interval::new(0.001, f64::INFINITY)) else {
+            return 0.0;
+        };
+
+        let distance_squared = hit.record.t_value * hit.record.t_value * direction.length_squared();
+        let cosine = direction.dot(self.normal).abs() / direction.length();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random_direction(&self, origin: Vec3) -> Vec3 {
+        let point =
+            self.q + self.u * util::get_random_canonical() + self.v * util::get_random_canonical();
+        point - origin
+    }
+}
+
+pub struct HittableList {
+    objects: Vec<Box<dyn Hittable>>,
+    bbox: AABB3,
+}
+
+impl Hittable for HittableList {
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        let mut current_hit = None;
+        let mut t_closest = t_range.max;
+
+        for object in self.objects.iter() {
+            if let Some(hit) = object.hit(ray.clone(), (t_range.min, t_closest).into()) {
+                t_closest = hit.record.t_value;
+                current_hit = Some(hit);
+            }
+        }
+
+        current_hit
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        &self.bbox
+    }
+
+    // every object is picked with the same probability
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let sum = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum::<f64>();
+        sum / self.objects.len() as f64
+    }
+
+    fn random_direction(&self, origin: Vec3) -> Vec3 {
+        let index = util::get_random(0, self.objects.len());
+        self.objects[index].random_direction(origin)
+    }
+}
+
+impl HittableList {
+    pub fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            bbox: AABB3::empty(),
+        }
+    }
+
+    pub fn add(&mut self, object: Box<dyn Hittable>) {
+        self.bbox.combine(object.bounding_box());
+        self.objects.push(object);
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Axis-aligned box with two opposite vertices `a` and `b`, made of 6 quads. A material is created
+// for each side.
+pub fn make_box<F>(a: Vec3, b: Vec3, material: F) -> HittableList
+where
+    F: Fn() -> Box<dyn Material>,
+{
+    let mut sides = HittableList::new();
+
+    let min = Vec3::new(array::from_fn(|i| a[i].min(b[i])));
+    let max = Vec3::new(array::from_fn(|i| a[i].max(b[i])));
+
+    let dx = Vec3::new([max[0] - min[0], 0.0, 0.0]);
+    let dy = Vec3::new([0.0, max[1] - min[1], 0.0]);
+    let dz = Vec3::new([0.0, 0.0, max[2] - min[2]]);
+
+    let [x0, y0, z0] = min.data;
+    let [x1, y1, z1] = max.data;
+
+    let sides_def = [
+        (Vec3::new([x0, y0, z1]), dx, dy),  // front
+        (Vec3::new([x1, y0, z1]), -dz, dy), // right
+        (Vec3::new([x1, y0, z0]), -dx, dy), // back
+        (Vec3::new([x0, y0, z0]), dz, dy),  // left
+        (Vec3::new([x0, y1, z1]), dx, -dz), // top
+        (Vec3::new([x0, y0, z0]), dx, dz),  // bottom
+    ];
+
+    for (q, u, v) in sides_def {
+        sides.add(Box::new(Quad::new(q, u, v, Some(material()))));
+    }
+
+    sides
+}
@@ -0,0 +1,43 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub mod aabb;
+pub mod color;
+pub mod hittable;
+pub mod interval;
+pub mod material;
+pub mod onb;
+pub mod pdf;
+pub mod progress_tracker;
+pub mod ray;
+pub mod ray_tracer;
+pub mod scenes;
+pub mod texture;
+pub mod transform;
+pub mod util;
+pub mod vec;
+
+use color::Color;
+use ray_tracer::{Dimension, Image};
+
+// ascii ppm, gamma corrected and clamped
+pub fn generate_ppm_image(image: &Image, path: &Path) -> io::Result<()> {
+    const MAX_COLOR: f64 = 255.0;
+
+    let Image { pixels, dimension } = image;
+    let Dimension { width, height } = dimension;
+
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "P3\n{} {}\n{}", width, height, MAX_COLOR)?;
+
+    for pixel in pixels {
+        let color: Color<i32> = pixel
+            .correct_gamma()
+            .clamp((0.0, 0.999).into())
+            .transform(|v| (v * (MAX_COLOR + 1.0)) as i32);
+        writeln!(file, "{} {} {}", color.r(), color.g(), color.b())?;
+    }
+
+    file.flush()
+}
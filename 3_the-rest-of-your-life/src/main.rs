@@ -1,3 +1,45 @@
+use std::path::Path;
+use std::time::Instant;
+
+use ray_tracing_the_rest_of_your_life as rtr;
+
+use rtr::ray_tracer::{RayTracer, TracerParams};
+use rtr::scenes::{self, Scene};
+use rtr::vec::Vector;
+
 fn main() {
-    println!("Hello, world!");
+    // usage: ray-tracing-the-rest-of-your-life [output] [scene]
+    let args = std::env::args().collect::<Vec<_>>();
+    let filename = args.get(1).map_or("image.ppm", |v| v.as_str());
+    let scene_name = args.get(2).map_or("cornell-box", |v| v.as_str());
+
+    let Some(scene) = scenes::find(scene_name) else {
+        let names = scenes::SCENES.map(|(name, _)| name).join(", ");
+        eprintln!("Unknown scene '{}', choose one of: {}", scene_name, names);
+        std::process::exit(1);
+    };
+    let Scene { world, lights } = scene();
+
+    let ray_tracer = RayTracer::new(TracerParams {
+        aspect_ratio: 1.0,
+        height: 600,
+        sampling_rate: 100,
+        max_depth: 50,
+        vfov: 40.0,
+        defocus_angle: 0.0,
+        focus_distance: 10.0,
+        look_from: Vector::new([278.0, 278.0, -800.0]),
+        look_at: Vector::new([278.0, 278.0, 0.0]),
+        ..Default::default()
+    });
+
+    let now = Instant::now();
+    let image = ray_tracer.render_multi(&world, &lights);
+    println!("Rendering took {:.2} seconds", now.elapsed().as_secs_f64());
+
+    let path = Path::new(filename);
+    if let Err(e) = rtr::generate_ppm_image(&image, path) {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
 }
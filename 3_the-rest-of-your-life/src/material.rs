@@ -0,0 +1,213 @@
+use std::f64::consts::PI;
+
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::pdf::{CosinePdf, Pdf, SpherePdf};
+use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+use crate::util;
+use crate::vec::{self, Vector};
+
+type Ray3 = Ray<f64, 3>;
+type Vec3 = Vector<f64, 3>;
+
+// where the scattered ray goes
+pub enum Scattered {
+    // the renderer picks the direction from the distribution (mixed with the directions towards
+    // the lights) and weights the ray with `Material::scattering_pdf` over the density
+    Pdf(Box<dyn Pdf>),
+    // a single direction (mirrors, glass), its density would be infinite so the ray is followed as
+    // it is
+    Specular(Ray3),
+}
+
+pub struct ScatterResult {
+    pub attenuation: Color,
+    pub scattered: Scattered,
+}
+
+pub trait Material: Send + Sync {
+    fn scatter(&self, ray: &Ray3, hit_record: &HitRecord) -> Option<ScatterResult>;
+
+    // Density (over solid angle) of the light scattered to `direction`, the attenuation times this
+    // is the reflectance times the cosine of the surface
+    fn scattering_pdf(&self, _ray: &Ray3, _hit_record: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // light emitted by the material at the hit point, black for non-emissive materials
+    fn emitted(&self, _ray: &Ray3, _hit_record: &HitRecord) -> Color {
+        Color::new_one(0.0)
+    }
+}
+
+// diffuse material
+pub struct Lambertian {
+    pub texture: Box<dyn Texture>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Color) -> Self {
+        Self {
+            texture: Box::new(SolidColor::new(albedo)),
+        }
+    }
+
+    pub fn with_texture(texture: Box<dyn Texture>) -> Self {
+        Self { texture }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(&self, _ray: &Ray3, hit_record: &HitRecord) -> Option<ScatterResult> {
+        Some(ScatterResult {
+            attenuation: self.texture.value(hit_record.tex, hit_record.point),
+            scattered: Scattered::Pdf(Box::new(CosinePdf::new(hit_record.normal))),
+        })
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray3, hit_record: &HitRecord, direction: Vec3) -> f64 {
+        let cosine = hit_record.normal.dot(direction.unit_vector());
+        cosine.max(0.0) / PI
+    }
+}
+
+// shiny material
+pub struct Metal {
+    pub albedo: Color,
+    pub fuzz: f64,
+}
+
+impl Metal {
+    pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self { albedo, fuzz }
+    }
+}
+
+impl Material for Metal {
+    fn scatter(&self, ray: &Ray3, hit_record: &HitRecord) -> Option<ScatterResult> {
+        let reflected = ray.direction.unit_vector().reflect(hit_record.normal)
+            + vec::random_in_unit_sphere() * self.fuzz;
+
+        match reflected.dot(hit_record.normal) {
+            x if x > 0.0 => Some(ScatterResult {
+                attenuation: self.albedo.clone(),
+                scattered: Scattered::Specular(Ray {
+                    origin: hit_record.point,
+                    direction: reflected,
+                    time: ray.time,
+                }),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// glassy material
+pub struct Dielectric {
+    pub refractive_index: f64,
+}
+
+impl Dielectric {
+    pub fn new(refractive_index: f64) -> Self {
+        Self { refractive_index }
+    }
+
+    fn reflectance(cosine: f64, refractive_index: f64) -> f64 {
+        // Schlick approx.
+        let mut r0 = (1.0 - refractive_index) / (1.0 + refractive_index);
+        r0 = r0 * r0;
+        r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray: &Ray3, hit_record: &HitRecord) -> Option<ScatterResult> {
+        let refraction_ratio = match hit_record.front_face {
+            true => 1.0 / self.refractive_index,
+            false => self.refractive_index,
+        };
+        let unit_direction = ray.direction.unit_vector();
+
+        // total internal reflection
+        let cos_theta = (-unit_direction).dot(hit_record.normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        let cannot_refract = refraction_ratio * sin_theta > 1.0
+            || Self::reflectance(cos_theta, refraction_ratio) > util::get_random_canonical();
+
+        let direction = match cannot_refract {
+            true => unit_direction.reflect(hit_record.normal),
+            false => unit_direction.refract(hit_record.normal, refraction_ratio),
+        };
+
+        Some(ScatterResult {
+            attenuation: Color::new_one(1.0),
+            scattered: Scattered::Specular(Ray {
+                origin: hit_record.point,
+                direction,
+                time: ray.time,
+            }),
+        })
+    }
+}
+
+// emissive material (area light), only emits from its front side and doesn't scatter any ray
+pub struct DiffuseLight {
+    pub texture: Box<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self {
+            texture: Box::new(SolidColor::new(emit)),
+        }
+    }
+
+    pub fn with_texture(texture: Box<dyn Texture>) -> Self {
+        Self { texture }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray3, _hit_record: &HitRecord) -> Option<ScatterResult> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray3, hit_record: &HitRecord) -> Color {
+        match hit_record.front_face {
+            true => self.texture.value(hit_record.tex, hit_record.point),
+            false => Color::new_one(0.0),
+        }
+    }
+}
+
+// scatters uniformly in every direction, the phase function of participating media
+pub struct Isotropic {
+    pub texture: Box<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self {
+            texture: Box::new(SolidColor::new(albedo)),
+        }
+    }
+
+    pub fn with_texture(texture: Box<dyn Texture>) -> Self {
+        Self { texture }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _ray: &Ray3, hit_record: &HitRecord) -> Option<ScatterResult> {
+        Some(ScatterResult {
+            attenuation: self.texture.value(hit_record.tex, hit_record.point),
+            scattered: Scattered::Pdf(Box::new(SpherePdf)),
+        })
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray3, _hit_record: &HitRecord, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
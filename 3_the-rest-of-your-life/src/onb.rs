@@ -0,0 +1,66 @@
+use crate::vec::Vector;
+
+type Vec3 = Vector<f64, 3>;
+
+// Orthonormal basis with `w` along a given direction, used to turn directions sampled around +z
+// (e.g. `vec::random_cosine_direction`) into directions around a normal
+#[derive(Clone, Debug)]
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        // any vector that is not parallel to w
+        let a = match w[0].abs() > 0.9 {
+            true => Vec3::new([0.0, 1.0, 0.0]),
+            false => Vec3::new([1.0, 0.0, 0.0]),
+        };
+        let v = w.cross(a).unit_vector();
+        let u = v.cross(w);
+
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    // from the coordinates in this basis to world coordinates
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        self.u() * v[0] + self.v() * v[1] + self.w() * v[2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_onb() {
+        for n in [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [-0.3, 2.0, 0.5]] {
+            let onb = Onb::new(n.into());
+            let [u, v, w] = onb.axis;
+
+            assert!((w - Vec3::from(n).unit_vector()).near_zero());
+            for (a, b) in [(u, v), (v, w), (w, u)] {
+                assert!(a.dot(b).abs() < 1e-12);
+            }
+            for axis in [u, v, w] {
+                assert!((axis.length() - 1.0).abs() < 1e-12);
+            }
+            // right handed
+            assert!((u.cross(v) - w).near_zero());
+            assert!((onb.transform(Vec3::new([0.0, 0.0, 2.0])) - w * 2.0).near_zero());
+        }
+    }
+}
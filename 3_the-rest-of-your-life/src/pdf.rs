@@ -0,0 +1,164 @@
+use std::f64::consts::PI;
+
+use crate::hittable::Hittable;
+use crate::onb::Onb;
+use crate::util;
+use crate::vec::{self, Vector};
+
+type Vec3 = Vector<f64, 3>;
+
+// A distribution of directions, used to pick where a ray scatters to. `value` is the density
+// (over solid angle) of `generate` choosing the direction.
+pub trait Pdf {
+    fn value(&self, direction: Vec3) -> f64;
+    fn generate(&self) -> Vec3;
+}
+
+// uniform over every direction
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        vec::random_unit_vector()
+    }
+}
+
+// cosine weighted around a normal, the distribution of a lambertian surface
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(normal: Vec3) -> Self {
+        Self {
+            uvw: Onb::new(normal),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let cosine = direction.unit_vector().dot(self.uvw.w());
+        cosine.max(0.0) / PI
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.uvw.transform(vec::random_cosine_direction())
+    }
+}
+
+// directions from `origin` towards the objects (see `Hittable::pdf_value`), used to send rays
+// to the lights
+pub struct HittablePdf<'a> {
+    objects: &'a dyn Hittable,
+    origin: Vec3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a dyn Hittable, origin: Vec3) -> Self {
+        Self { objects, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.objects.pdf_value(self.origin, direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.objects.random_direction(self.origin)
+    }
+}
+
+// picks one of the two distributions with the same probability
+pub struct MixturePdf<'a> {
+    pdfs: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(first: &'a dyn Pdf, second: &'a dyn Pdf) -> Self {
+        Self {
+            pdfs: [first, second],
+        }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        0.5 * self.pdfs[0].value(direction) + 0.5 * self.pdfs[1].value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        match util::get_random_canonical() < 0.5 {
+            true => self.pdfs[0].generate(),
+            false => self.pdfs[1].generate(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HittableList, Quad, Sphere};
+
+    // Monte Carlo estimate of the integral of the density over the sphere of directions
+    fn integral(pdf: &dyn Pdf) -> f64 {
+        let count = 50000;
+        let sum = (0..count)
+            .map(|_| pdf.value(vec::random_unit_vector()))
+            .sum::<f64>();
+        sum * 4.0 * PI / count as f64
+    }
+
+    #[test]
+    fn test_pdfs() {
+        util::seed_rng(1);
+        let mut lights = HittableList::new();
+        lights.add(Box::new(Quad::new(
+            [-1.0, 1.0, -1.0].into(),
+            [2.0, 0.0, 0.0].into(),
+            [0.0, 0.0, 2.0].into(),
+            None,
+        )));
+        lights.add(Box::new(Sphere::new([0.0, 0.0, -3.0].into(), 2.0, None)));
+
+        let origin = Vec3::new([0.0, 0.0, 0.0]);
+        let sphere = SpherePdf;
+        let cosine = CosinePdf::new(Vec3::new([0.0, 1.0, 1.0]));
+        let towards_lights = HittablePdf::new(&lights, origin);
+        let mixture = MixturePdf::new(&cosine, &towards_lights);
+        let pdfs: [&dyn Pdf; 4] = [&sphere, &cosine, &towards_lights, &mixture];
+
+        for pdf in pdfs {
+            // the generated directions are the ones with a density
+            for _ in 0..100 {
+                assert!(pdf.value(pdf.generate()) > 0.0);
+            }
+
+            let integral = integral(pdf);
+            assert!((integral - 1.0).abs() < 0.05, "{}", integral);
+        }
+    }
+
+    #[test]
+    fn test_cosine() {
+        util::seed_rng(2);
+        let normal = Vec3::new([1.0, 0.0, 0.0]);
+        let pdf = CosinePdf::new(normal);
+
+        assert!((pdf.value(normal * 3.0) - 1.0 / PI).abs() < 1e-12);
+        assert_eq!(pdf.value(-normal), 0.0);
+
+        // the mean of cos(theta) is 2/3 for a cosine weighted hemisphere
+        let count = 20000;
+        let mean = (0..count)
+            .map(|_| pdf.generate().unit_vector().dot(normal))
+            .sum::<f64>()
+            / count as f64;
+        assert!((mean - 2.0 / 3.0).abs() < 0.01, "{}", mean);
+    }
+}
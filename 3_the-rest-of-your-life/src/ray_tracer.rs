@@ -0,0 +1,448 @@
+use std::num::NonZeroUsize;
+use std::thread;
+
+use crate::color::Color;
+use crate::hittable::{HitResult, Hittable, HittableList};
+use crate::interval::Interval;
+use crate::material::{ScatterResult, Scattered};
+use crate::pdf::{HittablePdf, MixturePdf, Pdf};
+use crate::progress_tracker::ProgressTracker;
+use crate::ray::Ray;
+use crate::vec::Vector;
+use crate::{util, vec};
+
+type Vec3 = Vector<f64, 3>;
+type Ray3 = Ray<f64, 3>;
+
+#[derive(Clone, Debug)]
+pub struct Dimension {
+    pub width: u32,
+    pub height: u32,
+}
+
+// linear radiance, not clamped
+#[derive(Debug)]
+pub struct Image {
+    pub pixels: Vec<Color>,
+    pub dimension: Dimension,
+}
+
+#[derive(Debug)]
+pub struct TracerParams {
+    pub aspect_ratio: f64,
+    pub height: u32,
+    pub sampling_rate: u32,
+    pub max_depth: u32,
+    pub vfov: f64,
+    pub defocus_angle: f64,
+    pub focus_distance: f64,
+    pub look_from: Vec3,
+    pub look_at: Vec3,
+    // color of the rays that miss everything
+    pub background: Color,
+}
+
+#[derive(Debug)]
+struct Viewport {
+    pub du_vector: Vec3,
+    pub dv_vector: Vec3,
+    pub pixel_origin: Vec3,
+}
+
+#[derive(Debug)]
+struct Camera {
+    pub position: Vec3,
+    pub defocus_disk_u_vec: Vec3,
+    pub defocus_disk_v_vec: Vec3,
+    pub defocus_angle: f64,
+}
+
+#[derive(Debug)]
+pub struct RayTracer {
+    dimension: Dimension,
+    viewport: Viewport,
+    camera: Camera,
+    sampling_rate: u32,
+    max_depth: u32,
+    background: Color,
+}
+
+impl RayTracer {
+    pub fn new(params: TracerParams) -> Self {
+        let world_up = Vec3::new([0.0, 1.0, 0.0]);
+
+        let cam_center = params.look_from;
+
+        let view_dir = (cam_center - params.look_at).unit_vector();
+        let view_right = world_up.cross(view_dir).unit_vector();
+        let view_up = view_dir.cross(view_right);
+
+        let theta = params.vfov.to_radians();
+        let h = (theta / 2.0).tan();
+
+        let height = params.height;
+        let width = (height as f64 * params.aspect_ratio) as u32;
+
+        let actual_ratio = width as f64 / height as f64;
+        let view_height = 2.0 * h * params.focus_distance;
+        let view_width = view_height * actual_ratio;
+
+        let view_u_vec = view_right * view_width;
+        let view_v_vec = -view_up * view_height;
+        let view_du_vec = view_u_vec / width as f64;
+        let view_dv_vec = view_v_vec / height as f64;
+
+        let view_upper_left =
+            cam_center - (view_dir * params.focus_distance) - view_u_vec / 2.0 - view_v_vec / 2.0;
+        let view_pixel_origin = view_upper_left + (view_du_vec + view_dv_vec) * 0.5;
+
+        let defocus_radius =
+            params.focus_distance * (params.defocus_angle / 2.0).to_radians().tan();
+        let defocus_disk_u_vec = view_right * defocus_radius;
+        let defocus_disk_v_vec = view_up * defocus_radius;
+
+        // construct
+        let dimension = Dimension { width, height };
+
+        let viewport = Viewport {
+            du_vector: view_du_vec,
+            dv_vector: view_dv_vec,
+            pixel_origin: view_pixel_origin,
+        };
+
+        let camera = Camera {
+            position: cam_center,
+            defocus_disk_u_vec,
+            defocus_disk_v_vec,
+            defocus_angle: params.defocus_angle,
+        };
+
+        Self {
+            dimension,
+            viewport,
+            camera,
+            sampling_rate: params.sampling_rate,
+            max_depth: params.max_depth,
+            background: params.background,
+        }
+    }
+
+    // `lights` are the objects the diffuse rays are sent towards (half of them go to one of the
+    // lights), they must be part of the world as well
+    pub fn render(&self, world: &dyn Hittable, lights: &HittableList) -> Image {
+        let mut pixels = Vec::<Color>::with_capacity(
+            self.dimension.width as usize * self.dimension.height as usize,
+        );
+
+        let Dimension { width, height } = self.dimension;
+        let mut tracker = ProgressTrackerWrapper::new(width, height as usize);
+
+        for row in 0..height {
+            for col in 0..width {
+                pixels.push(self.sample_color_at(col, row, world, lights));
+
+                tracker.update(row as usize, (col + 1) as usize);
+            }
+        }
+
+        Image {
+            pixels,
+            dimension: self.dimension.clone(),
+        }
+    }
+
+    pub fn render_multi(&self, world: &dyn Hittable, lights: &HittableList) -> Image {
+        let concurrency_level: usize = thread::available_parallelism()
+            .unwrap_or(NonZeroUsize::new(1).unwrap())
+            .get();
+        let chunk_size = self.dimension.height as usize / concurrency_level;
+
+        enum SampleResult {
+            Color(usize, Color),
+            None,
+        }
+
+        let (tx, rx) = std::sync::mpsc::channel::<SampleResult>();
+
+        // interleaved rendering
+        thread::scope(|s| {
+            for i in 0..concurrency_level {
+                let num_steps = match chunk_size * concurrency_level + i {
+                    x if x < self.dimension.height as usize => chunk_size + 1,
+                    _ => chunk_size,
+                };
+                let tx = tx.clone();
+
+                s.spawn(move || {
+                    let mut tracker = match i {
+                        0 => Some(ProgressTrackerWrapper::new(self.dimension.width, num_steps)),
+                        _ => None,
+                    };
+
+                    for count in 0..num_steps {
+                        let row = (count * concurrency_level + i) as u32;
+                        for col in 0..self.dimension.width {
+                            let index = (row * self.dimension.width + col) as usize;
+                            let color = self.sample_color_at(col, row, world, lights);
+
+                            tx.send(SampleResult::Color(index, color)).unwrap();
+
+                            if let Some(tracker) = tracker.as_mut() {
+                                tracker.update(count, (col + 1) as usize);
+                            }
+                        }
+                    }
+                    tx.send(SampleResult::None).unwrap();
+                });
+            }
+        });
+
+        let pixel_num = self.dimension.width as usize * self.dimension.height as usize;
+        let mut pixels = vec![Color::new([0.0, 0.0, 0.0]); pixel_num];
+
+        let mut completed_threads = 0usize;
+        while completed_threads < concurrency_level {
+            match rx.recv().unwrap() {
+                SampleResult::Color(index, color) => pixels[index] = color,
+                SampleResult::None => completed_threads += 1,
+            }
+        }
+
+        Image {
+            pixels,
+            dimension: self.dimension.clone(),
+        }
+    }
+
+    fn sample_color_at(
+        &self,
+        col: u32,
+        row: u32,
+        world: &dyn Hittable,
+        lights: &HittableList,
+    ) -> Color {
+        let mut accumulated_color = Color::new_one(0.0);
+
+        let pixel_center = self.viewport.pixel_origin
+            + (self.viewport.du_vector * col as f64)
+            + (self.viewport.dv_vector * row as f64);
+
+        for _ in 0..self.sampling_rate {
+            let pixel_sample = pixel_center + self.sample_unit_square();
+            let ray_origin = match self.camera.defocus_angle {
+                x if x <= 0.0 => self.camera.position,
+                _ => self.defocus_disk_sample(),
+            };
+            let ray_direction = pixel_sample - ray_origin;
+            let ray_time = util::get_random_canonical();
+
+            let ray = Ray3 {
+                origin: ray_origin,
+                direction: ray_direction.unit_vector(),
+                time: ray_time,
+            };
+
+            accumulated_color =
+                accumulated_color + self.ray_color(ray, self.max_depth, world, lights);
+        }
+
+        accumulated_color / self.sampling_rate as f64
+    }
+
+    fn ray_color(
+        &self,
+        ray: Ray3,
+        depth: u32,
+        world: &dyn Hittable,
+        lights: &HittableList,
+    ) -> Color {
+        if depth == 0 {
+            return Color::new_one(0.0);
+        }
+
+        let (record, material) = match world.hit(ray.clone(), Interval::new(0.001, f64::INFINITY)) {
+            Some(HitResult {
+                record,
+                material: Some(material),
+            }) => (record, material),
+            // object without material, show its normal instead
+            Some(HitResult {
+                record,
+                material: None,
+            }) => return Color::from(record.normal * 0.5 + 0.5),
+            // missed, use background color instead
+            None => return self.background.clone(),
+        };
+
+        let emitted = material.emitted(&ray, &record);
+        let Some(ScatterResult {
+            attenuation,
+            scattered,
+        }) = material.scatter(&ray, &record)
+        else {
+            return emitted;
+        };
+
+        let material_pdf = match scattered {
+            Scattered::Specular(new_ray) => {
+                return emitted + attenuation * self.ray_color(new_ray, depth - 1, world, lights)
+            }
+            Scattered::Pdf(pdf) => pdf,
+        };
+
+        // half of the rays go to the lights, the other half follow the material
+        let light_pdf = HittablePdf::new(lights, record.point);
+        let mixture = MixturePdf::new(&light_pdf, material_pdf.as_ref());
+        let pdf: &dyn Pdf = match lights.is_empty() {
+            true => material_pdf.as_ref(),
+            false => &mixture,
+        };
+
+        let new_ray = Ray3 {
+            origin: record.point,
+            direction: pdf.generate(),
+            time: ray.time,
+        };
+        let pdf_value = pdf.value(new_ray.direction);
+        // the direction can't have been generated, nothing is scattered there
+        if pdf_value <= 0.0 {
+            return emitted;
+        }
+        let scattering_pdf = material.scattering_pdf(&ray, &record, new_ray.direction);
+
+        let scattered_color = attenuation * self.ray_color(new_ray, depth - 1, world, lights);
+        emitted + scattered_color * scattering_pdf / pdf_value
+    }
+
+    fn sample_unit_square(&self) -> Vec3 {
+        let px = util::get_random(0.0, 1.0) - 0.5;
+        let py = util::get_random(0.0, 1.0) - 0.5;
+        self.viewport.du_vector * px + self.viewport.dv_vector * py
+    }
+
+    fn defocus_disk_sample(&self) -> Vec3 {
+        let [x, y] = vec::random_in_unit_disk::<f64>().data;
+        self.camera.position
+            + self.camera.defocus_disk_u_vec * x
+            + self.camera.defocus_disk_v_vec * y
+    }
+}
+
+impl Default for TracerParams {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 16.0 / 9.0,
+            height: 480,
+            sampling_rate: 20,
+            max_depth: 10,
+            vfov: 20.0,
+            defocus_angle: 0.6,
+            focus_distance: 10.0,
+            look_from: Vector::new([13.0, 2.0, 3.0]),
+            look_at: Vector::new([0.0, 0.0, 0.0]),
+            background: Color::new_one(0.0),
+        }
+    }
+}
+
+struct ProgressTrackerWrapper {
+    tracker: ProgressTracker,
+    min_update_interval: usize,
+    width: usize,
+}
+
+impl ProgressTrackerWrapper {
+    pub fn new(width: u32, steps: usize) -> Self {
+        const MINIMUM_UPDATE_INTERVAL: usize = 512;
+        let max_count = steps * width as usize;
+        Self {
+            tracker: ProgressTracker::new(0, max_count as isize),
+            // min_update_interval: MINIMUM_UPDATE_INTERVAL.min(width as usize),
+            min_update_interval: MINIMUM_UPDATE_INTERVAL,
+            width: width as usize,
+        }
+    }
+
+    pub fn update(&mut self, count: usize, width_step: usize) {
+        let new_count = count * self.width + width_step;
+        let should_update = new_count.is_multiple_of(self.min_update_interval);
+        let reached_max = new_count == self.tracker.max() as usize;
+        if should_update || reached_max {
+            self.tracker.update(new_count as isize);
+            eprint!(
+                "Progress: {:>6.2}% | Elapsed: {:>6.2}s | ETA: {:>6.2}s\r",
+                self.tracker.progress(),
+                self.tracker.get_elapsed().as_secs_f64(),
+                self.tracker.get_eta().as_secs_f64()
+            );
+            if reached_max {
+                eprintln!();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes;
+
+    #[test]
+    fn test_light_sampling() {
+        util::seed_rng(3);
+        let params = |sampling_rate| TracerParams {
+            height: 16,
+            sampling_rate,
+            aspect_ratio: 1.0,
+            max_depth: 10,
+            vfov: 40.0,
+            defocus_angle: 0.0,
+            look_from: Vec3::new([278.0, 278.0, -800.0]),
+            look_at: Vec3::new([278.0, 278.0, 0.0]),
+            ..Default::default()
+        };
+        let render = |sampling_rate, light_sampling| {
+            let scene = scenes::cornell_box_diffuse();
+            let lights = match light_sampling {
+                true => scene.lights,
+                false => HittableList::new(),
+            };
+            RayTracer::new(params(sampling_rate))
+                .render(&scene.world, &lights)
+                .pixels
+        };
+        // clamped, the edge of the light is just as noisy either way and would hide the difference
+        let luminance = |pixels: &[Color]| {
+            pixels
+                .iter()
+                .map(|c| c.luminance().min(1.0))
+                .collect::<Vec<_>>()
+        };
+        let error = |pixels: &[f64], expected: &[f64]| {
+            pixels
+                .iter()
+                .zip(expected)
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<f64>()
+        };
+
+        let expected = luminance(&render(256, true));
+        let with_lights = luminance(&render(16, true));
+        let without_lights = luminance(&render(16, false));
+
+        // same image, with much less noise
+        let mean = |pixels: &[f64]| pixels.iter().sum::<f64>();
+        let relative = (mean(&with_lights) - mean(&expected)).abs() / mean(&expected);
+        assert!(relative < 0.15, "{}", relative);
+        let (with_error, without_error) = (
+            error(&with_lights, &expected),
+            error(&without_lights, &expected),
+        );
+        assert!(
+            with_error * 2.0 < without_error,
+            "{} {}",
+            with_error,
+            without_error
+        );
+    }
+}
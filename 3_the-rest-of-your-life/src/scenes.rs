@@ -0,0 +1,144 @@
+use crate::color::Color;
+use crate::hittable::{make_box, Hittable, HittableList, Quad, Sphere};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::transform::{Instance, Transform};
+use crate::vec::Vector;
+
+pub struct Scene {
+    pub world: HittableList,
+    // copies of the objects the rays are sent towards (lights, glass), without material
+    pub lights: HittableList,
+}
+
+type Function = fn() -> Scene;
+
+// all of them are best viewed from "278.0/278.0/-800.0" looking at "278.0/278.0/0.0" with vfov
+// of 40
+pub const SCENES: [(&str, Function); 2] = [
+    ("cornell-box", cornell_box as Function),
+    ("cornell-box-diffuse", cornell_box_diffuse as Function),
+];
+
+pub fn find(name: &str) -> Option<Function> {
+    SCENES
+        .iter()
+        .find(|(scene_name, _)| *scene_name == name)
+        .map(|(_, function)| *function)
+}
+
+type M = Box<dyn Material>;
+
+fn white() -> M {
+    Box::new(Lambertian::new(Color::new_one(0.73)))
+}
+
+// the red, green and white walls with the light in the ceiling, the light is added to `lights`
+fn cornell_box_room(lights: &mut HittableList) -> Vec<Box<dyn Hittable>> {
+    let red = Box::new(Lambertian::new(Color::new([0.65, 0.05, 0.05]))) as M;
+    let green = Box::new(Lambertian::new(Color::new([0.12, 0.45, 0.15]))) as M;
+    let light_material = Box::new(DiffuseLight::new(Color::new_one(15.0))) as M;
+
+    // facing down, the light only shines on its front side
+    let light = |material| {
+        Quad::new(
+            Vector::new([213.0, 554.0, 227.0]),
+            Vector::new([130.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, 105.0]),
+            material,
+        )
+    };
+    lights.add(Box::new(light(None)));
+
+    vec![
+        Box::new(Quad::new(
+            Vector::new([555.0, 0.0, 0.0]),
+            Vector::new([0.0, 555.0, 0.0]),
+            Vector::new([0.0, 0.0, 555.0]),
+            Some(green),
+        )),
+        Box::new(Quad::new(
+            Vector::new([0.0, 0.0, 0.0]),
+            Vector::new([0.0, 555.0, 0.0]),
+            Vector::new([0.0, 0.0, 555.0]),
+            Some(red),
+        )),
+        Box::new(light(Some(light_material))),
+        Box::new(Quad::new(
+            Vector::new([0.0, 0.0, 0.0]),
+            Vector::new([555.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, 555.0]),
+            Some(white()),
+        )),
+        Box::new(Quad::new(
+            Vector::new([555.0, 555.0, 555.0]),
+            Vector::new([-555.0, 0.0, 0.0]),
+            Vector::new([0.0, 0.0, -555.0]),
+            Some(white()),
+        )),
+        Box::new(Quad::new(
+            Vector::new([0.0, 0.0, 555.0]),
+            Vector::new([555.0, 0.0, 0.0]),
+            Vector::new([0.0, 555.0, 0.0]),
+            Some(white()),
+        )),
+    ]
+}
+
+fn rotated_box(
+    size: Vector<f64, 3>,
+    degrees: f64,
+    offset: Vector<f64, 3>,
+    material: fn() -> M,
+) -> Instance {
+    let sides = make_box(Vector::new([0.0, 0.0, 0.0]), size, material);
+    let transform = Transform::rotation_y(degrees).translate(offset);
+    Instance::new(Box::new(sides), transform)
+}
+
+// the scene at the end of the book: an aluminium box and a glass sphere, the rays are sent to
+// both the light and the sphere
+pub fn cornell_box() -> Scene {
+    let mut lights = HittableList::new();
+    let mut world = HittableList::new();
+    for object in cornell_box_room(&mut lights) {
+        world.add(object);
+    }
+
+    let aluminium = || Box::new(Metal::new(Color::new([0.8, 0.85, 0.88]), 0.0)) as M;
+    world.add(Box::new(rotated_box(
+        Vector::new([165.0, 330.0, 165.0]),
+        15.0,
+        Vector::new([265.0, 0.0, 295.0]),
+        aluminium,
+    )));
+
+    let sphere = |material| Sphere::new(Vector::new([190.0, 90.0, 190.0]), 90.0, material);
+    world.add(Box::new(sphere(Some(Box::new(Dielectric::new(1.5))))));
+    lights.add(Box::new(sphere(None)));
+
+    Scene { world, lights }
+}
+
+// the classic box with two white boxes, only the light is sampled
+pub fn cornell_box_diffuse() -> Scene {
+    let mut lights = HittableList::new();
+    let mut world = HittableList::new();
+    for object in cornell_box_room(&mut lights) {
+        world.add(object);
+    }
+
+    world.add(Box::new(rotated_box(
+        Vector::new([165.0, 330.0, 165.0]),
+        15.0,
+        Vector::new([265.0, 0.0, 295.0]),
+        white,
+    )));
+    world.add(Box::new(rotated_box(
+        Vector::new([165.0, 165.0, 165.0]),
+        -18.0,
+        Vector::new([130.0, 0.0, 65.0]),
+        white,
+    )));
+
+    Scene { world, lights }
+}
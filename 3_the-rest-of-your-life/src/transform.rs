@@ -0,0 +1,266 @@
+use std::array;
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::hittable::{HitResult, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec::Vector;
+
+type Vec3 = Vector<f64, 3>;
+type Ray3 = Ray<f64, 3>;
+type AABB3 = AABB<f64, 3>;
+
+// Affine transformation (a 4x4 matrix with the last row being [0, 0, 0, 1])
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform {
+    linear: [Vec3; 3], // rows of the 3x3 part
+    translation: Vec3,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            linear: array::from_fn(|i| Vec3::new(array::from_fn(|j| (i == j) as u8 as f64))),
+            translation: Vec3::default(),
+        }
+    }
+
+    pub fn from_parts(linear: [Vec3; 3], translation: Vec3) -> Self {
+        Self {
+            linear,
+            translation,
+        }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self {
+            translation: offset,
+            ..Self::identity()
+        }
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Self {
+            linear: array::from_fn(|i| {
+                Vec3::new(array::from_fn(|j| if i == j { factors[i] } else { 0.0 }))
+            }),
+            translation: Vec3::default(),
+        }
+    }
+
+    // rotation around an arbitrary axis (Rodrigues' formula), angle in degrees
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let [x, y, z] = axis.unit_vector().data;
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1.0 - cos;
+
+        Self {
+            linear: [
+                Vec3::new([cos + x * x * k, x * y * k - z * sin, x * z * k + y * sin]),
+                Vec3::new([y * x * k + z * sin, cos + y * y * k, y * z * k - x * sin]),
+                Vec3::new([z * x * k - y * sin, z * y * k + x * sin, cos + z * z * k]),
+            ],
+            translation: Vec3::default(),
+        }
+    }
+
+    pub fn rotation_x(degrees: f64) -> Self {
+        Self::rotation(Vec3::new([1.0, 0.0, 0.0]), degrees)
+    }
+
+    pub fn rotation_y(degrees: f64) -> Self {
+        Self::rotation(Vec3::new([0.0, 1.0, 0.0]), degrees)
+    }
+
+    pub fn rotation_z(degrees: f64) -> Self {
+        Self::rotation(Vec3::new([0.0, 0.0, 1.0]), degrees)
+    }
+
+    // `self` is applied first, then `other`
+    pub fn then(&self, other: &Transform) -> Self {
+        let columns = self.columns();
+        Self {
+            linear: array::from_fn(|i| {
+                Vec3::new(array::from_fn(|j| other.linear[i].dot(columns[j])))
+            }),
+            translation: other.transform_point(self.translation),
+        }
+    }
+
+    pub fn translate(&self, offset: Vec3) -> Self {
+        self.then(&Self::translation(offset))
+    }
+
+    pub fn scale(&self, factors: Vec3) -> Self {
+        self.then(&Self::scaling(factors))
+    }
+
+    pub fn rotate(&self, axis: Vec3, degrees: f64) -> Self {
+        self.then(&Self::rotation(axis, degrees))
+    }
+
+    pub fn rotate_x(&self, degrees: f64) -> Self {
+        self.then(&Self::rotation_x(degrees))
+    }
+
+    pub fn rotate_y(&self, degrees: f64) -> Self {
+        self.then(&Self::rotation_y(degrees))
+    }
+
+    pub fn rotate_z(&self, degrees: f64) -> Self {
+        self.then(&Self::rotation_z(degrees))
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let [r0, r1, r2] = self.linear;
+        r0.dot(r1.cross(r2))
+    }
+
+    // returns None if the transformation is degenerate (e.g. zero scaling)
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        // the inverse of a 3x3 matrix is the transposed cofactor matrix divided by the determinant,
+        // the cofactor rows are the cross products of the columns
+        let [c0, c1, c2] = self.columns();
+        let linear = [c1.cross(c2) / det, c2.cross(c0) / det, c0.cross(c1) / det];
+        let inverse_linear = Self {
+            linear,
+            translation: Vec3::default(),
+        };
+
+        Some(Self {
+            translation: -inverse_linear.transform_vector(self.translation),
+            linear,
+        })
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.transform_vector(point) + self.translation
+    }
+
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        Vec3::new(array::from_fn(|i| self.linear[i].dot(vector)))
+    }
+
+    // multiply by the transposed matrix, use it on the inverse transform to transform normals
+    pub fn transform_vector_transposed(&self, vector: Vec3) -> Vec3 {
+        let [r0, r1, r2] = self.linear;
+        r0 * vector[0] + r1 * vector[1] + r2 * vector[2]
+    }
+
+    pub fn transform_ray(&self, ray: &Ray3) -> Ray3 {
+        Ray {
+            origin: self.transform_point(ray.origin),
+            direction: self.transform_vector(ray.direction),
+            time: ray.time,
+        }
+    }
+
+    // bounding box of the transformed corners of the box
+    pub fn transform_aabb(&self, bbox: &AABB3) -> AABB3 {
+        let mut result = AABB3::empty();
+        for corner in 0..8 {
+            let point = Vec3::new(array::from_fn(|axis| {
+                let interval = bbox.axis_interval(axis);
+                match (corner >> axis) & 1 {
+                    0 => interval.min,
+                    _ => interval.max,
+                }
+            }));
+            let point = self.transform_point(point);
+            result.combine(&AABB3::from_points(point, point));
+        }
+        result
+    }
+
+    fn columns(&self) -> [Vec3; 3] {
+        array::from_fn(|j| Vec3::new(array::from_fn(|i| self.linear[i][j])))
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+// An object placed in the world with a transform. The object is defined in its own (object)
+// space, rays are brought into object space and the hit is brought back to world space.
+//
+// The object can be shared between many instances (e.g. a bottom-level bvh of a mesh), put the
+// instances in a top-level bvh to place the same geometry many times while storing it only once.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    to_world: Transform,
+    to_object: Transform,
+    bbox: AABB3,
+}
+
+impl Instance {
+    pub fn new(object: Box<dyn Hittable>, transform: Transform) -> Self {
+        Self::shared(Arc::from(object), transform)
+    }
+
+    pub fn shared(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let to_object = transform
+            .inverse()
+            .expect("Instance transform must be invertible");
+        let bbox = transform.transform_aabb(object.bounding_box());
+
+        Self {
+            object,
+            to_world: transform,
+            to_object,
+            bbox,
+        }
+    }
+
+    pub fn translated(object: Box<dyn Hittable>, offset: Vec3) -> Self {
+        Self::new(object, Transform::translation(offset))
+    }
+
+    pub fn rotated_y(object: Box<dyn Hittable>, degrees: f64) -> Self {
+        Self::new(object, Transform::rotation_y(degrees))
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.to_world
+    }
+
+    pub fn object(&self) -> &Arc<dyn Hittable> {
+        &self.object
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: Ray3, t_range: Interval) -> Option<HitResult<'_>> {
+        // the direction is not normalized, so t is the same in both spaces
+        let object_ray = self.to_object.transform_ray(&ray);
+        let HitResult {
+            mut record,
+            material,
+        } = self.object.hit(object_ray, t_range)?;
+
+        record.point = self.to_world.transform_point(record.point);
+        record.normal = self
+            .to_object
+            .transform_vector_transposed(record.normal)
+            .unit_vector();
+
+        Some(HitResult { record, material })
+    }
+
+    fn get_material(&self) -> Option<&dyn Material> {
+        self.object.get_material()
+    }
+
+    fn bounding_box(&self) -> &AABB3 {
+        &self.bbox
+    }
+}
@@ -0,0 +1,30 @@
+use std::cell::RefCell;
+
+use num::Num;
+use rand::distributions::uniform::SampleUniform;
+use rand::distributions::{Distribution, Uniform};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+thread_local! {
+    // every random value in the renderer comes from here, see `seed_rng`
+    static RNG: RefCell<ChaCha8Rng> = RefCell::new(ChaCha8Rng::from_entropy());
+}
+
+// restarts the random sequence of the current thread, the same seed always gives the same values
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = ChaCha8Rng::seed_from_u64(seed));
+}
+
+pub fn get_random_canonical() -> f64 {
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
+
+pub fn get_random<T: Num + SampleUniform>(from: T, to: T) -> T {
+    let dist = Uniform::<T>::new(from, to);
+    RNG.with(|rng| dist.sample(&mut *rng.borrow_mut()))
+}
+
+pub fn linear_to_gamma(linear: f64) -> f64 {
+    linear.sqrt()
+}